@@ -21,6 +21,7 @@
         rustc
         cargo
         cairo
+        dav1d
         gdk-pixbuf
         gobject-introspection
         graphene
@@ -0,0 +1,254 @@
+use gtk::prelude::*;
+use gtk::subclass::prelude::*;
+use gtk::{gdk, glib};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::time::Duration;
+
+use crate::frames::Frame;
+use crate::session_store::{Playback, PlaybackMode};
+
+type Textures = Rc<[(gdk::Texture, Duration)]>;
+
+// Key for a file decoded at a given short side, or at full size
+type CacheKey = (String, Option<u32>);
+
+thread_local! {
+    // Paintables playing with the default settings, one per file and size. Only weak
+    // references are kept so a paintable stops once nothing displays it.
+    static SHARED: RefCell<HashMap<CacheKey, glib::WeakRef<AnimatedPaintable>>> =
+        RefCell::new(HashMap::new());
+}
+
+mod imp {
+    use super::*;
+    use std::cell::Cell;
+
+    #[derive(Default)]
+    pub struct AnimatedPaintable {
+        pub textures: RefCell<Option<Textures>>,
+        pub index: Cell<usize>,
+        // Direction of travel while ping-ponging
+        pub backwards: Cell<bool>,
+        pub playback: Cell<Playback>,
+        pub shared: Cell<bool>,
+        pub source_id: RefCell<Option<glib::SourceId>>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for AnimatedPaintable {
+        const NAME: &'static str = "StickerbookAnimatedPaintable";
+        type Type = super::AnimatedPaintable;
+        type Interfaces = (gdk::Paintable,);
+    }
+
+    impl ObjectImpl for AnimatedPaintable {
+        fn dispose(&self) {
+            if let Some(id) = self.source_id.take() {
+                id.remove();
+            }
+        }
+    }
+
+    impl PaintableImpl for AnimatedPaintable {
+        fn current_image(&self) -> gdk::Paintable {
+            match self.current_texture() {
+                Some(texture) => texture.upcast(),
+                None => gdk::Paintable::new_empty(0, 0),
+            }
+        }
+
+        fn flags(&self) -> gdk::PaintableFlags {
+            // Every frame of an image has the same size
+            gdk::PaintableFlags::SIZE
+        }
+
+        fn intrinsic_width(&self) -> i32 {
+            self.current_texture()
+                .map_or(0, |texture| texture.intrinsic_width())
+        }
+
+        fn intrinsic_height(&self) -> i32 {
+            self.current_texture()
+                .map_or(0, |texture| texture.intrinsic_height())
+        }
+
+        fn snapshot(&self, snapshot: &gdk::Snapshot, width: f64, height: f64) {
+            if let Some(texture) = self.current_texture() {
+                texture.snapshot(snapshot, width, height);
+            }
+        }
+    }
+
+    impl AnimatedPaintable {
+        pub fn current_texture(&self) -> Option<gdk::Texture> {
+            let textures = self.textures.borrow();
+            let textures = textures.as_ref()?;
+            textures
+                .get(self.index.get())
+                .map(|(texture, _)| texture.clone())
+        }
+    }
+}
+
+glib::wrapper! {
+    /// A paintable that uploads every frame of an image once and advances
+    /// through them on each frame's own delay.
+    pub struct AnimatedPaintable(ObjectSubclass<imp::AnimatedPaintable>)
+        @implements gdk::Paintable;
+}
+
+/// Returns the paintable already playing `path` at `size` with default
+/// settings, if some window or thumbnail still shows it.
+pub fn lookup_shared(path: &str, size: Option<u32>) -> Option<AnimatedPaintable> {
+    SHARED.with(|shared| {
+        shared
+            .borrow()
+            .get(&(path.to_string(), size))
+            .and_then(|paintable| paintable.upgrade())
+    })
+}
+
+/// Uploads decoded frames and registers the result as the shared paintable
+/// for `path` at `size`.
+pub fn share(path: &str, size: Option<u32>, frames: &[Frame]) -> AnimatedPaintable {
+    let textures: Textures = frames
+        .iter()
+        .map(|frame| (frame.texture(), frame.delay))
+        .collect();
+    let paintable = AnimatedPaintable::new(textures, Playback::default(), 0);
+    paintable.imp().shared.set(true);
+    SHARED.with(|shared| {
+        let mut shared = shared.borrow_mut();
+        shared.retain(|_, paintable| paintable.upgrade().is_some());
+        shared.insert((path.to_string(), size), paintable.downgrade());
+    });
+    paintable
+}
+
+impl AnimatedPaintable {
+    fn new(textures: Textures, playback: Playback, index: usize) -> Self {
+        let paintable: Self = glib::Object::new();
+        let imp = paintable.imp();
+        imp.index.set(index.min(textures.len().saturating_sub(1)));
+        imp.textures.replace(Some(textures));
+        imp.playback.set(playback);
+        paintable.schedule();
+        paintable
+    }
+
+    /// Creates a paintable of its own that reuses this one's textures, for a
+    /// window whose playback settings differ from the shared defaults.
+    pub fn with_playback(&self, playback: Playback) -> Self {
+        let imp = self.imp();
+        let textures = imp.textures.borrow().clone().unwrap_or_else(|| Rc::new([]));
+        Self::new(textures, playback, imp.index.get())
+    }
+
+    pub fn is_shared(&self) -> bool {
+        self.imp().shared.get()
+    }
+
+    pub fn is_animated(&self) -> bool {
+        self.frame_count() > 1
+    }
+
+    fn frame_count(&self) -> usize {
+        self.imp()
+            .textures
+            .borrow()
+            .as_ref()
+            .map_or(0, |textures| textures.len())
+    }
+
+    pub fn set_paused(&self, paused: bool) {
+        self.update_playback(|playback| playback.paused = paused);
+    }
+
+    pub fn set_speed(&self, speed: f32) {
+        self.update_playback(|playback| playback.speed = speed.clamp(0.25, 4.0));
+    }
+
+    pub fn set_mode(&self, mode: PlaybackMode) {
+        self.imp().backwards.set(false);
+        self.update_playback(|playback| playback.mode = mode);
+    }
+
+    /// Pauses playback and moves one frame forwards or backwards.
+    pub fn step(&self, forward: bool) {
+        let len = self.frame_count();
+        if len > 1 {
+            let index = self.imp().index.get();
+            self.imp().index.set(if forward {
+                (index + 1) % len
+            } else {
+                (index + len - 1) % len
+            });
+            self.invalidate_contents();
+        }
+        self.set_paused(true);
+    }
+
+    fn update_playback(&self, update: impl FnOnce(&mut Playback)) {
+        let mut playback = self.imp().playback.get();
+        update(&mut playback);
+        self.imp().playback.set(playback);
+        self.schedule();
+    }
+
+    /// Replaces any pending frame timer with one for the current frame.
+    fn schedule(&self) {
+        let imp = self.imp();
+        if let Some(id) = imp.source_id.take() {
+            id.remove();
+        }
+        let playback = imp.playback.get();
+        if playback.paused || self.frame_count() < 2 {
+            return;
+        }
+
+        let delay = imp
+            .textures
+            .borrow()
+            .as_ref()
+            .and_then(|textures| textures.get(imp.index.get()))
+            .map_or(Duration::ZERO, |(_, delay)| *delay)
+            .div_f32(playback.speed.clamp(0.25, 4.0));
+        let paintable = self.downgrade();
+        let id = glib::timeout_add_local_once(delay, move || {
+            let Some(paintable) = paintable.upgrade() else {
+                return;
+            };
+            // The source has fired, so it must not be removed again
+            paintable.imp().source_id.take();
+            paintable.advance();
+            paintable.invalidate_contents();
+            paintable.schedule();
+        });
+        imp.source_id.replace(Some(id));
+    }
+
+    fn advance(&self) {
+        let imp = self.imp();
+        let len = self.frame_count();
+        let index = imp.index.get();
+        let next = match imp.playback.get().mode {
+            PlaybackMode::Loop => (index + 1) % len,
+            PlaybackMode::Reverse => (index + len - 1) % len,
+            PlaybackMode::PingPong => {
+                if !imp.backwards.get() && index + 1 >= len {
+                    imp.backwards.set(true);
+                } else if imp.backwards.get() && index == 0 {
+                    imp.backwards.set(false);
+                }
+                if imp.backwards.get() {
+                    index - 1
+                } else {
+                    index + 1
+                }
+            }
+        };
+        imp.index.set(next);
+    }
+}
@@ -0,0 +1,75 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+use crate::session_store::StickerState;
+
+/// A named set of sticker windows that can be opened and closed as one.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Board {
+    pub name: String,
+    pub stickers: Vec<StickerState>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct BoardStore {
+    boards: Vec<Board>,
+}
+
+impl BoardStore {
+    pub fn load() -> Self {
+        let config_path = Self::config_path();
+        if let Ok(content) = fs::read_to_string(&config_path)
+            && let Ok(store) = serde_json::from_str(&content)
+        {
+            return store;
+        }
+        Self::default()
+    }
+
+    pub fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let config_path = Self::config_path();
+        if let Some(parent) = config_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let content = serde_json::to_string_pretty(self)?;
+        fs::write(config_path, content)?;
+        Ok(())
+    }
+
+    pub fn boards(&self) -> &[Board] {
+        &self.boards
+    }
+
+    pub fn board(&self, name: &str) -> Option<&Board> {
+        self.boards.iter().find(|board| board.name == name)
+    }
+
+    /// Saves stickers under `name`, replacing any board already called that.
+    /// Returns false if the name is empty.
+    pub fn set_board(&mut self, name: &str, stickers: Vec<StickerState>) -> bool {
+        let name = name.trim();
+        if name.is_empty() {
+            return false;
+        }
+        match self.boards.iter_mut().find(|board| board.name == name) {
+            Some(board) => board.stickers = stickers,
+            None => self.boards.push(Board {
+                name: name.to_string(),
+                stickers,
+            }),
+        }
+        true
+    }
+
+    pub fn remove_board(&mut self, name: &str) {
+        self.boards.retain(|board| board.name != name);
+    }
+
+    fn config_path() -> PathBuf {
+        let mut path = dirs::config_dir().unwrap_or_else(|| PathBuf::from("."));
+        path.push("stickerbook");
+        path.push("boards.json");
+        path
+    }
+}
@@ -0,0 +1,385 @@
+use gtk::prelude::*;
+use gtk::{gdk, glib};
+use image::{ImageFormat, RgbaImage};
+use libadwaita as adw;
+use libadwaita::prelude::*;
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::Duration;
+
+use crate::frames::{self, Frame};
+use crate::main_window;
+
+// The preview is worked out on a copy this small so the sliders stay responsive
+const PREVIEW_SIZE: u32 = 512;
+// Colours just outside the tolerance fade out rather than stopping at a hard edge
+const SOFT_EDGE: f32 = 0.05;
+
+/// How the background to remove is picked out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CutoutMode {
+    MagicWand,
+    ChromaKey,
+}
+
+impl CutoutMode {
+    pub const ALL: [CutoutMode; 2] = [CutoutMode::MagicWand, CutoutMode::ChromaKey];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            CutoutMode::MagicWand => "Magic Wand From Corners",
+            CutoutMode::ChromaKey => "Chroma Key",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Settings {
+    mode: CutoutMode,
+    key: [u8; 3],
+    // Largest colour distance still removed, from 0 to 1
+    tolerance: f32,
+}
+
+/// Returns a copy of `image` with its background made transparent.
+fn apply(image: &RgbaImage, settings: Settings) -> RgbaImage {
+    let mut image = image.clone();
+    match settings.mode {
+        CutoutMode::MagicWand => flood_from_corners(&mut image, settings.tolerance),
+        CutoutMode::ChromaKey => chroma_key(&mut image, settings.key, settings.tolerance),
+    }
+    image
+}
+
+/// Distance between two colours, scaled so black to white is 1.
+fn distance(pixel: &image::Rgba<u8>, key: [u8; 3]) -> f32 {
+    let sum: f32 = (0..3)
+        .map(|channel| (pixel[channel] as f32 - key[channel] as f32).powi(2))
+        .sum();
+    sum.sqrt() / (255.0 * 3f32.sqrt())
+}
+
+/// Clears the background touching the image's edges: from each corner, spreads
+/// to neighbouring pixels whose colour is close to that corner's.
+fn flood_from_corners(image: &mut RgbaImage, tolerance: f32) {
+    let (width, height) = image.dimensions();
+    if width == 0 || height == 0 {
+        return;
+    }
+    // One bit per corner, so each corner's fill can still test pixels another rejected
+    let mut seen = vec![0u8; width as usize * height as usize];
+    let corners = [
+        (0, 0),
+        (width - 1, 0),
+        (0, height - 1),
+        (width - 1, height - 1),
+    ];
+
+    for (bit, &(x, y)) in corners.iter().enumerate() {
+        let bit = 1 << bit;
+        let seed = *image.get_pixel(x, y);
+        let key = [seed[0], seed[1], seed[2]];
+        let mut queue = VecDeque::from([(x, y)]);
+        seen[(y * width + x) as usize] |= bit;
+
+        while let Some((x, y)) = queue.pop_front() {
+            let pixel = image.get_pixel_mut(x, y);
+            // Pass through areas that are already transparent
+            if pixel[3] != 0 && distance(pixel, key) > tolerance {
+                continue;
+            }
+            pixel[3] = 0;
+
+            let neighbours = [
+                (x.wrapping_sub(1), y),
+                (x + 1, y),
+                (x, y.wrapping_sub(1)),
+                (x, y + 1),
+            ];
+            for (nx, ny) in neighbours {
+                if nx >= width || ny >= height {
+                    continue;
+                }
+                let index = (ny * width + nx) as usize;
+                if seen[index] & bit == 0 {
+                    seen[index] |= bit;
+                    queue.push_back((nx, ny));
+                }
+            }
+        }
+    }
+}
+
+/// Clears every pixel close to the key colour, wherever it is in the image.
+fn chroma_key(image: &mut RgbaImage, key: [u8; 3], tolerance: f32) {
+    for pixel in image.pixels_mut() {
+        let distance = distance(pixel, key);
+        if distance <= tolerance {
+            pixel[3] = 0;
+        } else if distance < tolerance + SOFT_EDGE {
+            let keep = (distance - tolerance) / SOFT_EDGE;
+            pixel[3] = (pixel[3] as f32 * keep).round() as u8;
+        }
+    }
+}
+
+fn encode_png(image: &RgbaImage) -> Result<Vec<u8>, image::ImageError> {
+    let mut png = Cursor::new(Vec::new());
+    image.write_to(&mut png, ImageFormat::Png)?;
+    Ok(png.into_inner())
+}
+
+/// Opens a dialog for cutting the background out of the image at `path`,
+/// previewing the result as the settings change. `on_save` is given the
+/// finished image as PNG data.
+pub fn present(parent: &impl IsA<gtk::Widget>, path: &Path, on_save: impl Fn(Vec<u8>) + 'static) {
+    // Full-size image and the reduced copy the preview is made from
+    let source: Rc<RefCell<Option<RgbaImage>>> = Rc::new(RefCell::new(None));
+    let preview_source: Rc<RefCell<Option<RgbaImage>>> = Rc::new(RefCell::new(None));
+    let settings = Rc::new(Cell::new(Settings {
+        mode: CutoutMode::MagicWand,
+        key: [255, 255, 255],
+        tolerance: 0.1,
+    }));
+
+    let picture = gtk::Picture::builder()
+        .content_fit(gtk::ContentFit::Contain)
+        .width_request(360)
+        .height_request(360)
+        .vexpand(true)
+        .build();
+    let frame = gtk::Frame::builder().child(&picture).build();
+
+    let mode_labels: Vec<&str> = CutoutMode::ALL.iter().map(|mode| mode.label()).collect();
+    let mode_dropdown = gtk::DropDown::from_strings(&mode_labels);
+    mode_dropdown.set_hexpand(true);
+    let color_button =
+        gtk::ColorDialogButton::new(Some(gtk::ColorDialog::builder().with_alpha(false).build()));
+    color_button.set_tooltip_text(Some("Color to remove"));
+    color_button.set_sensitive(false);
+    let mode_row = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+    mode_row.append(&mode_dropdown);
+    mode_row.append(&color_button);
+
+    let tolerance_scale = gtk::Scale::with_range(gtk::Orientation::Horizontal, 0.0, 100.0, 1.0);
+    tolerance_scale.set_value((settings.get().tolerance * 100.0) as f64);
+    tolerance_scale.set_draw_value(true);
+    tolerance_scale.set_value_pos(gtk::PositionType::Right);
+    tolerance_scale.set_format_value_func(|_, value| format!("{value:.0}%"));
+    tolerance_scale.set_hexpand(true);
+    let tolerance_row = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+    tolerance_row.append(&gtk::Label::new(Some("Tolerance")));
+    tolerance_row.append(&tolerance_scale);
+
+    let hint = gtk::Label::builder()
+        .label("Click the image to pick the color to remove")
+        .wrap(true)
+        .build();
+    hint.add_css_class("dim-label");
+    hint.add_css_class("caption");
+
+    let content = gtk::Box::new(gtk::Orientation::Vertical, 6);
+    content.set_margin_start(12);
+    content.set_margin_end(12);
+    content.set_margin_top(6);
+    content.set_margin_bottom(12);
+    content.append(&frame);
+    content.append(&mode_row);
+    content.append(&tolerance_row);
+    content.append(&hint);
+
+    let save_button = gtk::Button::with_label("Save");
+    save_button.add_css_class("suggested-action");
+    save_button.set_sensitive(false);
+    let headerbar = adw::HeaderBar::new();
+    headerbar.pack_end(&save_button);
+
+    let toolbar_view = adw::ToolbarView::new();
+    toolbar_view.add_top_bar(&headerbar);
+    toolbar_view.set_content(Some(&content));
+
+    let dialog = adw::Dialog::builder()
+        .title("Remove Background")
+        .content_width(480)
+        .child(&toolbar_view)
+        .build();
+
+    let update = {
+        let preview_source = preview_source.clone();
+        let settings = settings.clone();
+        let picture = picture.clone();
+        Rc::new(move || {
+            if let Some(image) = preview_source.borrow().as_ref() {
+                let frame = Frame {
+                    image: apply(image, settings.get()),
+                    delay: Duration::ZERO,
+                };
+                picture.set_paintable(Some(&frame.texture()));
+            }
+        })
+    };
+
+    let settings_mode = settings.clone();
+    let update_mode = update.clone();
+    let color_button_mode = color_button.clone();
+    mode_dropdown.connect_selected_notify(move |dropdown| {
+        let Some(mode) = CutoutMode::ALL.get(dropdown.selected() as usize) else {
+            return;
+        };
+        color_button_mode.set_sensitive(*mode == CutoutMode::ChromaKey);
+        let mut current = settings_mode.get();
+        current.mode = *mode;
+        settings_mode.set(current);
+        update_mode();
+    });
+
+    let settings_color = settings.clone();
+    let update_color = update.clone();
+    color_button.connect_rgba_notify(move |button| {
+        let rgba = button.rgba();
+        let channel = |value: f32| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+        let mut current = settings_color.get();
+        current.key = [
+            channel(rgba.red()),
+            channel(rgba.green()),
+            channel(rgba.blue()),
+        ];
+        settings_color.set(current);
+        update_color();
+    });
+
+    let settings_tolerance = settings.clone();
+    let update_tolerance = update.clone();
+    tolerance_scale.connect_value_changed(move |scale| {
+        let mut current = settings_tolerance.get();
+        current.tolerance = (scale.value() / 100.0) as f32;
+        settings_tolerance.set(current);
+        update_tolerance();
+    });
+
+    // Clicking the preview picks the colour under the pointer as the key
+    let click = gtk::GestureClick::new();
+    let preview_source_click = preview_source.clone();
+    let picture_click = picture.clone();
+    let color_button_click = color_button.clone();
+    let mode_dropdown_click = mode_dropdown.clone();
+    click.connect_released(move |_, _, x, y| {
+        let preview_source = preview_source_click.borrow();
+        let Some(image) = preview_source.as_ref() else {
+            return;
+        };
+        // Undo the letterboxing of the contained picture
+        let (image_width, image_height) = image.dimensions();
+        let width = picture_click.width() as f64;
+        let height = picture_click.height() as f64;
+        let scale = (width / image_width as f64).min(height / image_height as f64);
+        let image_x = (x - (width - image_width as f64 * scale) / 2.0) / scale;
+        let image_y = (y - (height - image_height as f64 * scale) / 2.0) / scale;
+        if image_x < 0.0
+            || image_y < 0.0
+            || image_x >= image_width as f64
+            || image_y >= image_height as f64
+        {
+            return;
+        }
+        let pixel = image.get_pixel(image_x as u32, image_y as u32);
+        color_button_click.set_rgba(&gdk::RGBA::new(
+            pixel[0] as f32 / 255.0,
+            pixel[1] as f32 / 255.0,
+            pixel[2] as f32 / 255.0,
+            1.0,
+        ));
+        if let Some(index) = CutoutMode::ALL
+            .iter()
+            .position(|mode| *mode == CutoutMode::ChromaKey)
+        {
+            mode_dropdown_click.set_selected(index as u32);
+        }
+    });
+    picture.add_controller(click);
+
+    // Work at full size only when saving
+    let source_save = source.clone();
+    let settings_save = settings.clone();
+    let dialog_save = dialog.clone();
+    let on_save = Rc::new(on_save);
+    save_button.connect_clicked(move |button| {
+        let Some(image) = source_save.borrow().clone() else {
+            return;
+        };
+        let settings = settings_save.get();
+        let dialog = dialog_save.clone();
+        let on_save = on_save.clone();
+        let button = button.clone();
+        button.set_sensitive(false);
+        glib::spawn_future_local(async move {
+            let error =
+                match gio::spawn_blocking(move || encode_png(&apply(&image, settings))).await {
+                    Ok(Ok(png)) => {
+                        on_save(png);
+                        dialog.close();
+                        return;
+                    }
+                    Ok(Err(err)) => {
+                        eprintln!("Failed to encode cut-out image: {err}");
+                        err.to_string()
+                    }
+                    Err(_) => {
+                        eprintln!("Background removal panicked");
+                        "Something went wrong while removing the background".to_string()
+                    }
+                };
+            // Leave the dialog open so saving can be tried again
+            button.set_sensitive(true);
+            main_window::show_error(&dialog, "Could Not Save Image", &error);
+        });
+    });
+
+    // Decode off the main thread; animated images contribute their first frame
+    let path: PathBuf = path.to_path_buf();
+    let save_button_load = save_button.clone();
+    let dialog_load = dialog.clone();
+    let parent_load: gtk::Widget = parent.clone().upcast();
+    glib::spawn_future_local(async move {
+        let path_decode = path.clone();
+        let decoded = gio::spawn_blocking(move || frames::decode(&path_decode)).await;
+        let loaded = match decoded {
+            Ok(Ok(frames)) => match frames.into_iter().next() {
+                Some(frame) => Ok(frame.image),
+                None => Err("The image has no frames".to_string()),
+            },
+            Ok(Err(err)) => {
+                eprintln!("Failed to load {}: {err}", path.display());
+                Err(err.to_string())
+            }
+            Err(_) => {
+                eprintln!("Decoder panicked on {}", path.display());
+                Err(format!(
+                    "Something went wrong while reading {}",
+                    path.display()
+                ))
+            }
+        };
+        // There is nothing to work on, so the reason replaces the empty dialog
+        let image = match loaded {
+            Ok(image) => image,
+            Err(error) => {
+                dialog_load.close();
+                main_window::show_error(&parent_load, "Could Not Remove Background", &error);
+                return;
+            }
+        };
+        preview_source.replace(Some(frames::scale_to_short_side(
+            image.clone(),
+            PREVIEW_SIZE,
+        )));
+        source.replace(Some(image));
+        save_button_load.set_sensitive(true);
+        update();
+    });
+
+    dialog.present(Some(parent));
+}
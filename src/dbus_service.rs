@@ -0,0 +1,149 @@
+use gtk::prelude::*;
+use gtk::{glib, Application};
+use std::cell::RefCell;
+use std::path::Path;
+use std::rc::Rc;
+
+use crate::main_window;
+use crate::recent_store::RecentStore;
+use crate::session_store::StickerState;
+use crate::sticker_window::{self, StickerHandle};
+
+const OBJECT_PATH: &str = "/com/github/toasterrepair/Stickerbook";
+const ERROR_NOT_FOUND: &str = "com.github.toasterrepair.Stickerbook.Error.NotFound";
+const ERROR_INVALID_ARGS: &str = "org.freedesktop.DBus.Error.InvalidArgs";
+
+const INTERFACE_XML: &str = r#"
+<node>
+  <interface name="com.github.toasterrepair.Stickerbook">
+    <method name="OpenSticker">
+      <arg type="s" name="path" direction="in"/>
+      <arg type="i" name="x" direction="in"/>
+      <arg type="i" name="y" direction="in"/>
+      <arg type="d" name="scale" direction="in"/>
+      <arg type="u" name="id" direction="out"/>
+    </method>
+    <method name="Close">
+      <arg type="u" name="id" direction="in"/>
+    </method>
+    <method name="CloseAll"/>
+    <method name="UnlockAll"/>
+    <method name="ListOpen">
+      <arg type="a(usd)" name="stickers" direction="out"/>
+    </method>
+    <method name="SetScale">
+      <arg type="u" name="id" direction="in"/>
+      <arg type="d" name="scale" direction="in"/>
+    </method>
+  </interface>
+</node>
+"#;
+
+/// Exports the remote-control interface on the application's bus connection,
+/// operating on the same sticker window registry as the rest of the app.
+pub fn register(
+    app: &Application,
+    recent_store: Rc<RefCell<RecentStore>>,
+    child_windows: Rc<RefCell<Vec<StickerHandle>>>,
+) -> Result<gio::RegistrationId, glib::Error> {
+    let Some(connection) = app.dbus_connection() else {
+        return Err(glib::Error::new(
+            gio::IOErrorEnum::NotConnected,
+            "Application is not on the session bus",
+        ));
+    };
+    let node_info = gio::DBusNodeInfo::for_xml(INTERFACE_XML)?;
+    let interface_info = node_info
+        .lookup_interface(crate::APP_ID)
+        .expect("Interface is declared in INTERFACE_XML");
+
+    let app = app.downgrade();
+    connection
+        .register_object(OBJECT_PATH, &interface_info)
+        .method_call(move |_, _, _, _, method, parameters, invocation| {
+            let Some(app) = app.upgrade() else {
+                return;
+            };
+            match method {
+                "OpenSticker" => {
+                    let Some((path, _x, _y, scale)) = parameters.get::<(String, i32, i32, f64)>()
+                    else {
+                        return invocation.return_dbus_error(ERROR_INVALID_ARGS, "Bad arguments");
+                    };
+                    if !Path::new(&path).is_file() {
+                        return invocation
+                            .return_dbus_error(ERROR_NOT_FOUND, &format!("No such file: {path}"));
+                    }
+
+                    let stored_path = recent_store.borrow_mut().import(Path::new(&path));
+                    let _ = recent_store.borrow().save();
+                    main_window::notify_library_changed(&app);
+
+                    let mut state = StickerState::new(stored_path);
+                    state.scale = (scale as f32).clamp(0.2, 5.0);
+                    let handle =
+                        sticker_window::create_sticker_window(&app, state, child_windows.clone());
+                    let id = handle.id;
+                    child_windows.borrow_mut().push(handle);
+                    invocation.return_value(Some(&(id,).to_variant()));
+                }
+                "Close" => {
+                    let Some((id,)) = parameters.get::<(u32,)>() else {
+                        return invocation.return_dbus_error(ERROR_INVALID_ARGS, "Bad arguments");
+                    };
+                    let Some(handle) = find(&child_windows, id) else {
+                        return invocation
+                            .return_dbus_error(ERROR_NOT_FOUND, &format!("No sticker {id}"));
+                    };
+                    handle.window.close();
+                    invocation.return_value(None);
+                }
+                "CloseAll" => {
+                    let children: Vec<_> = child_windows.borrow().iter().cloned().collect();
+                    for child in children {
+                        child.window.close();
+                    }
+                    invocation.return_value(None);
+                }
+                "UnlockAll" => {
+                    app.activate_action("unlock-stickers", None);
+                    invocation.return_value(None);
+                }
+                "ListOpen" => {
+                    let stickers: Vec<(u32, String, f64)> = child_windows
+                        .borrow()
+                        .iter()
+                        .map(|handle| {
+                            let state = handle.state.borrow();
+                            (handle.id, state.path.clone(), state.scale as f64)
+                        })
+                        .collect();
+                    invocation.return_value(Some(&(stickers,).to_variant()));
+                }
+                "SetScale" => {
+                    let Some((id, scale)) = parameters.get::<(u32, f64)>() else {
+                        return invocation.return_dbus_error(ERROR_INVALID_ARGS, "Bad arguments");
+                    };
+                    let Some(handle) = find(&child_windows, id) else {
+                        return invocation
+                            .return_dbus_error(ERROR_NOT_FOUND, &format!("No sticker {id}"));
+                    };
+                    handle.set_scale(scale as f32);
+                    invocation.return_value(None);
+                }
+                _ => invocation.return_dbus_error(
+                    "org.freedesktop.DBus.Error.UnknownMethod",
+                    &format!("Unknown method {method}"),
+                ),
+            }
+        })
+        .build()
+}
+
+fn find(child_windows: &Rc<RefCell<Vec<StickerHandle>>>, id: u32) -> Option<StickerHandle> {
+    child_windows
+        .borrow()
+        .iter()
+        .find(|handle| handle.id == id)
+        .cloned()
+}
@@ -0,0 +1,211 @@
+use gdk_pixbuf::Pixbuf;
+use gtk::prelude::*;
+use gtk::{gdk, glib};
+use image::codecs::avif::AvifDecoder;
+use image::codecs::gif::GifDecoder;
+use image::codecs::png::PngDecoder;
+use image::codecs::webp::WebPDecoder;
+use image::imageops::FilterType;
+use image::{AnimationDecoder, DynamicImage, ImageFormat, RgbaImage};
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+use std::time::Duration;
+
+use crate::lottie;
+
+pub type DecodeResult = Result<Vec<Frame>, Box<dyn std::error::Error + Send + Sync>>;
+
+// Browsers treat tiny frame delays as "as fast as possible" and slow them down
+const MIN_FRAME_DELAY: Duration = Duration::from_millis(20);
+const DEFAULT_FRAME_DELAY: Duration = Duration::from_millis(100);
+
+/// One decoded frame of a sticker as straight (non-premultiplied) RGBA.
+/// Frames are plain data so they can be decoded off the main thread.
+#[derive(Clone)]
+pub struct Frame {
+    pub image: RgbaImage,
+    pub delay: Duration,
+}
+
+impl Frame {
+    pub fn texture(&self) -> gdk::Texture {
+        let (width, height) = self.image.dimensions();
+        let bytes = glib::Bytes::from(self.image.as_raw().as_slice());
+        gdk::MemoryTexture::new(
+            width as i32,
+            height as i32,
+            gdk::MemoryFormat::R8g8b8a8,
+            &bytes,
+            width as usize * 4,
+        )
+        .upcast()
+    }
+}
+
+/// Decodes every frame of an image at full size. Still images yield one frame.
+pub fn decode(path: &Path) -> DecodeResult {
+    decode_frames(path, None)
+}
+
+/// Decodes an image scaled down so its shorter side is at most `size` pixels.
+pub fn decode_thumbnail(path: &Path, size: u32) -> DecodeResult {
+    let frames = decode_frames(path, Some(size))?;
+    Ok(frames
+        .into_iter()
+        .map(|frame| Frame {
+            image: scale_to_short_side(frame.image, size),
+            delay: frame.delay,
+        })
+        .collect())
+}
+
+/// Renders an image to fit within `width` × `height` pixels. Used for vector
+/// images, which the pixbuf loader draws sharply at any size.
+pub fn decode_at_size(path: &Path, width: i32, height: i32) -> DecodeResult {
+    let pixbuf = Pixbuf::from_file_at_size(path, width, height)?;
+    Ok(vec![Frame {
+        image: pixbuf_to_rgba(&pixbuf)?,
+        delay: Duration::ZERO,
+    }])
+}
+
+pub fn is_svg(path: &Path) -> bool {
+    let mut header = [0u8; 256];
+    let len = File::open(path)
+        .and_then(|mut file| file.read(&mut header))
+        .unwrap_or(0);
+    let (content_type, _) = gio::content_type_guess(Some(path), &header[..len]);
+    content_type.starts_with("image/svg")
+}
+
+fn guess_format(path: &Path) -> Option<ImageFormat> {
+    let mut header = [0u8; 32];
+    let mut file = File::open(path).ok()?;
+    let len = file.read(&mut header).ok()?;
+    image::guess_format(&header[..len]).ok()
+}
+
+/// Picks a decoder by the file's contents. Animated formats use the pure-Rust
+/// decoders from `image` or the Lottie renderer, since gdk-pixbuf only animates
+/// GIFs; `short_side` lets still images be decoded at a reduced size.
+fn decode_frames(path: &Path, short_side: Option<u32>) -> DecodeResult {
+    if lottie::is_tgs(path) {
+        return lottie::decode(path, short_side);
+    }
+    match guess_format(path) {
+        Some(ImageFormat::Gif) => decode_animation(GifDecoder::new(open(path)?)?),
+        Some(ImageFormat::Png) => {
+            let decoder = PngDecoder::new(open(path)?)?;
+            if decoder.is_apng()? {
+                decode_animation(decoder.apng()?)
+            } else {
+                decode_pixbuf(path, short_side)
+            }
+        }
+        Some(ImageFormat::WebP) => {
+            let decoder = WebPDecoder::new(open(path)?)?;
+            if decoder.has_animation() {
+                decode_animation(decoder)
+            } else {
+                // Not every system has a WebP pixbuf loader
+                Ok(vec![Frame {
+                    image: DynamicImage::from_decoder(decoder)?.into_rgba8(),
+                    delay: Duration::ZERO,
+                }])
+            }
+        }
+        // Decoded with dav1d, so no AVIF pixbuf loader is needed. Only the still
+        // image is read; an animated AVIF shows the image it carries for viewers
+        // that can't play it.
+        Some(ImageFormat::Avif) => {
+            let image = AvifDecoder::new(open(path)?).and_then(DynamicImage::from_decoder);
+            match image {
+                Ok(image) => Ok(vec![Frame {
+                    image: image.into_rgba8(),
+                    delay: Duration::ZERO,
+                }]),
+                // Sequences with no still image are left to a pixbuf loader, if any
+                Err(_) => decode_pixbuf(path, short_side),
+            }
+        }
+        // Everything else goes through the installed pixbuf loaders
+        _ => decode_pixbuf(path, short_side),
+    }
+}
+
+fn open(path: &Path) -> std::io::Result<BufReader<File>> {
+    Ok(BufReader::new(File::open(path)?))
+}
+
+/// Collects the fully composited frames of an animated GIF, APNG or WebP.
+fn decode_animation<'a>(decoder: impl AnimationDecoder<'a>) -> DecodeResult {
+    let frames = decoder.into_frames().collect_frames()?;
+    Ok(frames
+        .into_iter()
+        .map(|frame| {
+            let delay = Duration::from(frame.delay());
+            Frame {
+                delay: if delay < MIN_FRAME_DELAY {
+                    DEFAULT_FRAME_DELAY
+                } else {
+                    delay
+                },
+                image: frame.into_buffer(),
+            }
+        })
+        .collect())
+}
+
+/// Loads a still image through gdk-pixbuf, which covers every format the
+/// installed pixbuf loaders understand.
+fn decode_pixbuf(path: &Path, short_side: Option<u32>) -> DecodeResult {
+    let pixbuf = match (short_side, Pixbuf::file_info(path)) {
+        // Let the loader decode at a reduced size where it can (JPEG does)
+        (Some(size), Some((_, width, height))) if width.min(height) > size as i32 => {
+            let scale = size as f64 / width.min(height) as f64;
+            Pixbuf::from_file_at_scale(
+                path,
+                (width as f64 * scale).round() as i32,
+                (height as f64 * scale).round() as i32,
+                true,
+            )?
+        }
+        _ => Pixbuf::from_file(path)?,
+    };
+    Ok(vec![Frame {
+        image: pixbuf_to_rgba(&pixbuf)?,
+        delay: Duration::ZERO,
+    }])
+}
+
+fn pixbuf_to_rgba(pixbuf: &Pixbuf) -> Result<RgbaImage, Box<dyn std::error::Error + Send + Sync>> {
+    let pixbuf = if pixbuf.has_alpha() {
+        pixbuf.clone()
+    } else {
+        pixbuf.add_alpha(false, 0, 0, 0)?
+    };
+    let width = pixbuf.width() as usize;
+    let height = pixbuf.height() as usize;
+    let rowstride = pixbuf.rowstride() as usize;
+    let bytes = pixbuf.read_pixel_bytes();
+
+    let mut data = Vec::with_capacity(width * height * 4);
+    for row in 0..height {
+        let start = row * rowstride;
+        data.extend_from_slice(&bytes[start..start + width * 4]);
+    }
+    RgbaImage::from_raw(width as u32, height as u32, data).ok_or_else(|| "bad pixbuf".into())
+}
+
+pub fn scale_to_short_side(image: RgbaImage, size: u32) -> RgbaImage {
+    let (width, height) = image.dimensions();
+    let short_side = width.min(height);
+    if short_side <= size {
+        return image;
+    }
+    let scale = size as f64 / short_side as f64;
+    let new_width = ((width as f64 * scale).round() as u32).max(1);
+    let new_height = ((height as f64 * scale).round() as u32).max(1);
+    image::imageops::resize(&image, new_width, new_height, FilterType::Triangle)
+}
@@ -0,0 +1,48 @@
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Copies an image into the library directory, naming the copy after the
+/// SHA-256 of its contents, and returns the path of the copy. Importing the
+/// same image twice reuses the existing copy.
+pub fn import(path: &Path) -> io::Result<PathBuf> {
+    let dir = library_dir();
+    if path.parent() == Some(dir.as_path()) {
+        return Ok(path.to_path_buf());
+    }
+
+    let bytes = fs::read(path)?;
+    let extension = path
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_string());
+    import_bytes(&bytes, extension.as_deref())
+}
+
+/// Stores image data that has no file of its own (a dropped texture or a
+/// downloaded URI) in the library, the same way `import` stores a copy.
+pub fn import_bytes(bytes: &[u8], extension: Option<&str>) -> io::Result<PathBuf> {
+    let dir = library_dir();
+    let mut file_name = format!("{:x}", Sha256::digest(bytes));
+    if let Some(extension) = extension {
+        file_name.push('.');
+        file_name.push_str(&extension.to_lowercase());
+    }
+
+    let target = dir.join(file_name);
+    if !target.exists() {
+        fs::create_dir_all(&dir)?;
+        // Write to a temporary name first so a crash never leaves a partial copy
+        let partial = target.with_extension("partial");
+        fs::write(&partial, bytes)?;
+        fs::rename(&partial, &target)?;
+    }
+    Ok(target)
+}
+
+pub fn library_dir() -> PathBuf {
+    let mut path = dirs::data_dir().unwrap_or_else(|| PathBuf::from("."));
+    path.push("stickerbook");
+    path.push("library");
+    path
+}
@@ -0,0 +1,854 @@
+use flate2::read::GzDecoder;
+use image::RgbaImage;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+use std::time::Duration;
+use tiny_skia::{
+    Color, FillRule, GradientStop, LineCap, LineJoin, LinearGradient, Paint, Path as SkPath,
+    PathBuilder, Pixmap, Point, RadialGradient, Rect, Shader, SpreadMode, Stroke, Transform,
+};
+
+use crate::frames::{DecodeResult, Frame};
+
+// Telegram stickers run at up to 60 fps; keeping every frame of a three second
+// sticker at full size would take hundreds of megabytes, so frames are sampled
+// at this rate at most. The animation keeps its real duration either way.
+const MAX_FRAME_RATE: f64 = 30.0;
+
+// Guards against parents that refer back to themselves and precompositions
+// nested absurdly deep
+const MAX_DEPTH: usize = 16;
+// Precompositions can each use another many times over, so the layers drawn
+// for one frame grow exponentially with nesting unless they are counted
+const MAX_LAYER_VISITS: usize = 10_000;
+
+// Telegram rejects stickers longer than three seconds or 180 frames, and draws
+// them at 512 pixels. Imported files are untrusted, so anything well past that
+// is refused rather than rendered.
+const MAX_DURATION: f64 = 3.0;
+const MAX_FRAMES: f64 = 180.0;
+const MAX_CANVAS_SIZE: f64 = 1024.0;
+
+/// Returns true for gzip data, which is how Telegram stores its animated
+/// stickers (`.tgs` files are gzipped Lottie JSON).
+pub fn is_tgs(path: &Path) -> bool {
+    let mut header = [0u8; 2];
+    File::open(path)
+        .and_then(|mut file| file.read_exact(&mut header))
+        .is_ok()
+        && header == [0x1f, 0x8b]
+}
+
+/// Renders every frame of a Telegram animated sticker with a small software
+/// Lottie renderer, scaled down so its shorter side is at most `short_side`.
+///
+/// Shape, solid, null and precomposition layers are drawn with their fills,
+/// strokes and gradients. Masks, mattes, trim paths and effects are ignored.
+pub fn decode(path: &Path, short_side: Option<u32>) -> DecodeResult {
+    let mut json = String::new();
+    GzDecoder::new(BufReader::new(File::open(path)?)).read_to_string(&mut json)?;
+    let root: Value = serde_json::from_str(&json)?;
+    Animation::new(&root)?.render(short_side)
+}
+
+struct Animation<'a> {
+    width: f64,
+    height: f64,
+    frame_rate: f64,
+    in_point: f64,
+    out_point: f64,
+    layers: &'a [Value],
+    // Precompositions by asset id
+    assets: HashMap<&'a str, &'a [Value]>,
+}
+
+impl<'a> Animation<'a> {
+    fn new(root: &'a Value) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let positive = |value: &Value| value.as_f64().filter(|value| *value > 0.0);
+        let width = positive(&root["w"]).ok_or("Lottie file has no width")?;
+        let height = positive(&root["h"]).ok_or("Lottie file has no height")?;
+        let frame_rate = positive(&root["fr"]).ok_or("Lottie file has no frame rate")?;
+        let in_point = root["ip"].as_f64().unwrap_or(0.0);
+        let out_point = root["op"].as_f64().ok_or("Lottie file has no end frame")?;
+        if width > MAX_CANVAS_SIZE || height > MAX_CANVAS_SIZE {
+            return Err(format!("Lottie canvas of {width}×{height} is too large").into());
+        }
+        let frame_count = out_point - in_point;
+        if frame_count > MAX_FRAMES || frame_count / frame_rate > MAX_DURATION {
+            return Err("Lottie animation is too long".into());
+        }
+
+        let assets = root["assets"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|asset| Some((asset["id"].as_str()?, array(&asset["layers"])?)))
+            .collect();
+
+        Ok(Self {
+            width,
+            height,
+            frame_rate,
+            in_point,
+            out_point,
+            layers: array(&root["layers"]).unwrap_or(&[]),
+            assets,
+        })
+    }
+
+    fn render(&self, short_side: Option<u32>) -> DecodeResult {
+        let scale = short_side.map_or(1.0, |size| {
+            (size as f64 / self.width.min(self.height)).min(1.0)
+        });
+        let width = ((self.width * scale).round() as u32).max(1);
+        let height = ((self.height * scale).round() as u32).max(1);
+
+        let step = (self.frame_rate / MAX_FRAME_RATE).ceil().max(1.0);
+        let delay = Duration::from_secs_f64(step / self.frame_rate);
+        let transform = Transform::from_scale(scale as f32, scale as f32);
+
+        let mut frames = Vec::new();
+        let mut frame = self.in_point;
+        while frame < self.out_point {
+            let mut pixmap = Pixmap::new(width, height).ok_or("Lottie canvas is too large")?;
+            self.render_layers(
+                &mut pixmap,
+                self.layers,
+                frame,
+                transform,
+                1.0,
+                &mut Walk::default(),
+            );
+            frames.push(Frame {
+                image: to_rgba(&pixmap)?,
+                delay,
+            });
+            frame += step;
+        }
+
+        if frames.is_empty() {
+            return Err("Lottie animation has no frames".into());
+        }
+        Ok(frames)
+    }
+
+    /// Draws a list of layers bottom to top; Lottie lists the topmost layer first.
+    fn render_layers(
+        &self,
+        pixmap: &mut Pixmap,
+        layers: &'a [Value],
+        frame: f64,
+        transform: Transform,
+        opacity: f64,
+        walk: &mut Walk<'a>,
+    ) {
+        if walk.assets.len() > MAX_DEPTH {
+            return;
+        }
+
+        for layer in layers.iter().rev() {
+            walk.visits += 1;
+            if walk.visits > MAX_LAYER_VISITS {
+                return;
+            }
+            // Hidden layers and the sources of track mattes are not drawn themselves
+            if layer["hd"].as_bool() == Some(true) || layer["td"].as_i64().unwrap_or(0) != 0 {
+                continue;
+            }
+            let in_point = layer["ip"].as_f64().unwrap_or(f64::MIN);
+            let out_point = layer["op"].as_f64().unwrap_or(f64::MAX);
+            if frame < in_point || frame >= out_point {
+                continue;
+            }
+
+            let local_frame = frame - layer["st"].as_f64().unwrap_or(0.0);
+            let transform = transform.pre_concat(layer_transform(layers, layer, frame, 0));
+            let opacity = opacity * transform_opacity(&layer["ks"], local_frame);
+            if opacity <= 0.0 {
+                continue;
+            }
+
+            match layer["ty"].as_i64() {
+                Some(0) => {
+                    let Some((id, children)) = layer["refId"]
+                        .as_str()
+                        .and_then(|id| Some((id, *self.assets.get(id)?)))
+                    else {
+                        continue;
+                    };
+                    // A precomposition that contains itself is drawn only once
+                    if walk.assets.contains(&id) {
+                        continue;
+                    }
+                    walk.assets.push(id);
+                    self.render_layers(pixmap, children, local_frame, transform, opacity, walk);
+                    walk.assets.pop();
+                }
+                Some(1) => render_solid(pixmap, layer, transform, opacity),
+                Some(4) => render_shapes(pixmap, &layer["shapes"], local_frame, transform, opacity),
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Tracks the drawing of one frame through nested precompositions.
+#[derive(Default)]
+struct Walk<'a> {
+    // Ids of the precompositions being drawn, outermost first
+    assets: Vec<&'a str>,
+    // Layers looked at so far
+    visits: usize,
+}
+
+/// A layer's transform including those of its parents.
+fn layer_transform(layers: &[Value], layer: &Value, frame: f64, depth: usize) -> Transform {
+    let own = transform_matrix(&layer["ks"], frame - layer["st"].as_f64().unwrap_or(0.0));
+    let parent = layer["parent"].as_i64().and_then(|index| {
+        layers
+            .iter()
+            .find(|other| other["ind"].as_i64() == Some(index))
+    });
+    match parent {
+        Some(parent) if depth < MAX_DEPTH => {
+            layer_transform(layers, parent, frame, depth + 1).pre_concat(own)
+        }
+        _ => own,
+    }
+}
+
+fn transform_matrix(transform: &Value, frame: f64) -> Transform {
+    let anchor = pair(value(&transform["a"], frame), 0.0);
+    let position = if transform["p"]["s"].as_bool() == Some(true) {
+        // Position split into separately animated x and y
+        [
+            scalar(&transform["p"]["x"], frame, 0.0),
+            scalar(&transform["p"]["y"], frame, 0.0),
+        ]
+    } else {
+        pair(value(&transform["p"], frame), 0.0)
+    };
+    let scale = pair(value(&transform["s"], frame), 100.0);
+    let rotation = if transform.get("r").is_some() {
+        scalar(&transform["r"], frame, 0.0)
+    } else {
+        scalar(&transform["rz"], frame, 0.0)
+    };
+
+    Transform::from_translate(position[0] as f32, position[1] as f32)
+        .pre_rotate(rotation as f32)
+        .pre_scale((scale[0] / 100.0) as f32, (scale[1] / 100.0) as f32)
+        .pre_translate(-anchor[0] as f32, -anchor[1] as f32)
+}
+
+fn transform_opacity(transform: &Value, frame: f64) -> f64 {
+    (scalar(&transform["o"], frame, 100.0) / 100.0).clamp(0.0, 1.0)
+}
+
+fn render_solid(pixmap: &mut Pixmap, layer: &Value, transform: Transform, opacity: f64) {
+    let (Some(width), Some(height)) = (layer["sw"].as_f64(), layer["sh"].as_f64()) else {
+        return;
+    };
+    let Some(rect) = Rect::from_xywh(0.0, 0.0, width as f32, height as f32) else {
+        return;
+    };
+    let hex = layer["sc"]
+        .as_str()
+        .unwrap_or("#000000")
+        .trim_start_matches('#');
+    let channel = |index: usize| {
+        hex.get(index..index + 2)
+            .and_then(|digits| u8::from_str_radix(digits, 16).ok())
+            .unwrap_or(0)
+    };
+
+    let mut paint = Paint::default();
+    paint.set_color_rgba8(channel(0), channel(2), channel(4), 255);
+    paint.shader.apply_opacity(opacity as f32);
+    paint.anti_alias = true;
+    pixmap.fill_rect(rect, &paint, transform, None);
+}
+
+enum DrawKind {
+    Fill(FillRule),
+    Stroke(Stroke),
+}
+
+/// A fill or stroke over the shapes that come before it in its group.
+struct DrawOp {
+    path: SkPath,
+    transform: Transform,
+    paint: Paint<'static>,
+    kind: DrawKind,
+}
+
+fn render_shapes(
+    pixmap: &mut Pixmap,
+    shapes: &Value,
+    frame: f64,
+    transform: Transform,
+    opacity: f64,
+) {
+    let mut ops = Vec::new();
+    collect_shapes(
+        array(shapes).unwrap_or(&[]),
+        frame,
+        transform,
+        opacity,
+        &mut ops,
+    );
+
+    // Items earlier in a group are drawn on top
+    for op in ops.iter().rev() {
+        match &op.kind {
+            DrawKind::Fill(rule) => {
+                pixmap.fill_path(&op.path, &op.paint, *rule, op.transform, None)
+            }
+            DrawKind::Stroke(stroke) => {
+                pixmap.stroke_path(&op.path, &op.paint, stroke, op.transform, None)
+            }
+        }
+    }
+}
+
+/// Walks a group's items in order, queueing a draw for every fill and stroke,
+/// and returns the group's paths in its own coordinates so the styles of
+/// enclosing groups apply to them as well.
+fn collect_shapes(
+    items: &[Value],
+    frame: f64,
+    transform: Transform,
+    opacity: f64,
+    ops: &mut Vec<DrawOp>,
+) -> Vec<SkPath> {
+    let mut paths = Vec::new();
+    for item in items {
+        if item["hd"].as_bool() == Some(true) {
+            continue;
+        }
+        match item["ty"].as_str() {
+            Some("gr") => {
+                let children = array(&item["it"]).unwrap_or(&[]);
+                let (local, group_opacity) = children
+                    .iter()
+                    .find(|child| child["ty"].as_str() == Some("tr"))
+                    .map_or((Transform::identity(), 1.0), |group_transform| {
+                        (
+                            transform_matrix(group_transform, frame),
+                            transform_opacity(group_transform, frame),
+                        )
+                    });
+                let child_paths = collect_shapes(
+                    children,
+                    frame,
+                    transform.pre_concat(local),
+                    opacity * group_opacity,
+                    ops,
+                );
+                paths.extend(
+                    child_paths
+                        .into_iter()
+                        .filter_map(|path| path.transform(local)),
+                );
+            }
+            Some("sh") => paths.extend(shape(&item["ks"], frame).and_then(|shape| shape.to_path())),
+            Some("el") => {
+                let center = pair(value(&item["p"], frame), 0.0);
+                let size = pair(value(&item["s"], frame), 0.0);
+                paths.extend(
+                    Rect::from_xywh(
+                        (center[0] - size[0] / 2.0) as f32,
+                        (center[1] - size[1] / 2.0) as f32,
+                        size[0] as f32,
+                        size[1] as f32,
+                    )
+                    .and_then(PathBuilder::from_oval),
+                );
+            }
+            Some("rc") => {
+                let center = pair(value(&item["p"], frame), 0.0);
+                let size = pair(value(&item["s"], frame), 0.0);
+                let radius = scalar(&item["r"], frame, 0.0);
+                paths.extend(rounded_rect(
+                    center[0] - size[0] / 2.0,
+                    center[1] - size[1] / 2.0,
+                    size[0],
+                    size[1],
+                    radius,
+                ));
+            }
+            Some("fl" | "st" | "gf" | "gs") => {
+                ops.extend(style_op(item, &paths, frame, transform, opacity));
+            }
+            _ => {}
+        }
+    }
+    paths
+}
+
+fn style_op(
+    item: &Value,
+    paths: &[SkPath],
+    frame: f64,
+    transform: Transform,
+    opacity: f64,
+) -> Option<DrawOp> {
+    let mut builder = PathBuilder::new();
+    for path in paths {
+        builder.push_path(path);
+    }
+    let path = builder.finish()?;
+
+    let kind = item["ty"].as_str()?;
+    let mut shader = match kind {
+        "fl" | "st" => Shader::SolidColor(color(&value(&item["c"], frame), 1.0)?),
+        _ => gradient(item, frame)?,
+    };
+    let alpha = opacity * (scalar(&item["o"], frame, 100.0) / 100.0).clamp(0.0, 1.0);
+    shader.apply_opacity(alpha as f32);
+
+    let paint = Paint {
+        shader,
+        anti_alias: true,
+        ..Paint::default()
+    };
+    let kind = match kind {
+        "fl" | "gf" => DrawKind::Fill(if item["r"].as_i64() == Some(2) {
+            FillRule::EvenOdd
+        } else {
+            FillRule::Winding
+        }),
+        _ => DrawKind::Stroke(Stroke {
+            width: scalar(&item["w"], frame, 1.0) as f32,
+            miter_limit: item["ml"].as_f64().unwrap_or(4.0) as f32,
+            line_cap: match item["lc"].as_i64() {
+                Some(2) => LineCap::Round,
+                Some(3) => LineCap::Square,
+                _ => LineCap::Butt,
+            },
+            line_join: match item["lj"].as_i64() {
+                Some(2) => LineJoin::Round,
+                Some(3) => LineJoin::Bevel,
+                _ => LineJoin::Miter,
+            },
+            dash: None,
+        }),
+    };
+
+    Some(DrawOp {
+        path,
+        transform,
+        paint,
+        kind,
+    })
+}
+
+/// Builds a linear or radial gradient. Colour stops are `[offset, r, g, b]`
+/// groups, optionally followed by `[offset, alpha]` pairs.
+fn gradient(item: &Value, frame: f64) -> Option<Shader<'static>> {
+    let data = value(&item["g"]["k"], frame);
+    // Each stop takes four numbers, so the data bounds how many there can be
+    let count = item["g"]["p"].as_u64()?.min((data.len() / 4) as u64) as usize;
+    let stops: Vec<GradientStop> = (0..count)
+        .filter_map(|index| {
+            let stop = data.get(index * 4..index * 4 + 4)?;
+            let alpha = data.get(count * 4 + index * 2 + 1).copied().unwrap_or(1.0);
+            Some(GradientStop::new(stop[0] as f32, color(&stop[1..], alpha)?))
+        })
+        .collect();
+
+    let start = pair(value(&item["s"], frame), 0.0);
+    let end = pair(value(&item["e"], frame), 0.0);
+    let start = Point::from_xy(start[0] as f32, start[1] as f32);
+    let end = Point::from_xy(end[0] as f32, end[1] as f32);
+    if item["t"].as_i64() == Some(2) {
+        RadialGradient::new(
+            start,
+            start,
+            (end - start).length(),
+            stops,
+            SpreadMode::Pad,
+            Transform::identity(),
+        )
+    } else {
+        LinearGradient::new(start, end, stops, SpreadMode::Pad, Transform::identity())
+    }
+}
+
+fn color(components: &[f64], alpha: f64) -> Option<Color> {
+    let [red, green, blue] = components.get(..3)?.try_into().ok()?;
+    // Some exporters write colours as 0-255 rather than 0-1
+    let range = if red > 1.0 || green > 1.0 || blue > 1.0 {
+        255.0
+    } else {
+        1.0
+    };
+    let channel = |value: f64| (value / range).clamp(0.0, 1.0) as f32;
+    Color::from_rgba(
+        channel(red),
+        channel(green),
+        channel(blue),
+        alpha.clamp(0.0, 1.0) as f32,
+    )
+}
+
+fn rounded_rect(x: f64, y: f64, width: f64, height: f64, radius: f64) -> Option<SkPath> {
+    let radius = radius.min(width / 2.0).min(height / 2.0);
+    if radius <= 0.0 {
+        let rect = Rect::from_xywh(x as f32, y as f32, width as f32, height as f32)?;
+        return Some(PathBuilder::from_rect(rect));
+    }
+
+    // Quarter circles approximated by cubic curves
+    let (x, y, width, height, radius) = (
+        x as f32,
+        y as f32,
+        width as f32,
+        height as f32,
+        radius as f32,
+    );
+    let handle = radius * 0.552_284_8;
+    let (right, bottom) = (x + width, y + height);
+    let mut builder = PathBuilder::new();
+    builder.move_to(x + radius, y);
+    builder.line_to(right - radius, y);
+    builder.cubic_to(
+        right - radius + handle,
+        y,
+        right,
+        y + radius - handle,
+        right,
+        y + radius,
+    );
+    builder.line_to(right, bottom - radius);
+    builder.cubic_to(
+        right,
+        bottom - radius + handle,
+        right - radius + handle,
+        bottom,
+        right - radius,
+        bottom,
+    );
+    builder.line_to(x + radius, bottom);
+    builder.cubic_to(
+        x + radius - handle,
+        bottom,
+        x,
+        bottom - radius + handle,
+        x,
+        bottom - radius,
+    );
+    builder.line_to(x, y + radius);
+    builder.cubic_to(
+        x,
+        y + radius - handle,
+        x + radius - handle,
+        y,
+        x + radius,
+        y,
+    );
+    builder.close();
+    builder.finish()
+}
+
+/// A Lottie path: vertices with tangents relative to them.
+struct Bezier {
+    vertices: Vec<[f64; 2]>,
+    in_tangents: Vec<[f64; 2]>,
+    out_tangents: Vec<[f64; 2]>,
+    closed: bool,
+}
+
+impl Bezier {
+    fn parse(value: &Value) -> Option<Self> {
+        // Keyframed shapes wrap the path in a one-element array
+        let value = match value.as_array() {
+            Some(values) => values.first()?,
+            None => value,
+        };
+        let points = |key: &str| -> Vec<[f64; 2]> {
+            array(&value[key])
+                .unwrap_or(&[])
+                .iter()
+                .map(|point| pair(numbers(point), 0.0))
+                .collect()
+        };
+        let vertices = points("v");
+        let in_tangents = points("i");
+        let out_tangents = points("o");
+        if in_tangents.len() != vertices.len() || out_tangents.len() != vertices.len() {
+            return None;
+        }
+        Some(Self {
+            vertices,
+            in_tangents,
+            out_tangents,
+            closed: value["c"].as_bool().unwrap_or(false),
+        })
+    }
+
+    fn lerp(&self, other: &Bezier, t: f64) -> Bezier {
+        let mix = |from: &[[f64; 2]], to: &[[f64; 2]]| {
+            from.iter()
+                .zip(to)
+                .map(|(a, b)| [a[0] + (b[0] - a[0]) * t, a[1] + (b[1] - a[1]) * t])
+                .collect()
+        };
+        Bezier {
+            vertices: mix(&self.vertices, &other.vertices),
+            in_tangents: mix(&self.in_tangents, &other.in_tangents),
+            out_tangents: mix(&self.out_tangents, &other.out_tangents),
+            closed: self.closed,
+        }
+    }
+
+    fn to_path(&self) -> Option<SkPath> {
+        let first = self.vertices.first()?;
+        let mut builder = PathBuilder::new();
+        builder.move_to(first[0] as f32, first[1] as f32);
+
+        let count = self.vertices.len();
+        let segments = if self.closed { count } else { count - 1 };
+        for index in 0..segments {
+            let next = (index + 1) % count;
+            let from = self.vertices[index];
+            let to = self.vertices[next];
+            let out_tangent = self.out_tangents[index];
+            let in_tangent = self.in_tangents[next];
+            builder.cubic_to(
+                (from[0] + out_tangent[0]) as f32,
+                (from[1] + out_tangent[1]) as f32,
+                (to[0] + in_tangent[0]) as f32,
+                (to[1] + in_tangent[1]) as f32,
+                to[0] as f32,
+                to[1] as f32,
+            );
+        }
+        if self.closed {
+            builder.close();
+        }
+        builder.finish()
+    }
+}
+
+fn shape(property: &Value, frame: f64) -> Option<Bezier> {
+    let value = &property["k"];
+    match keyframes(value) {
+        Some(keyframes) => {
+            let (from, to, t) = segment(keyframes, frame)?;
+            let from = Bezier::parse(from)?;
+            match Bezier::parse(to) {
+                Some(to) if to.vertices.len() == from.vertices.len() => Some(from.lerp(&to, t)),
+                _ => Some(from),
+            }
+        }
+        None => Bezier::parse(value),
+    }
+}
+
+/// Evaluates an animatable property, which holds either a fixed value or a
+/// list of keyframes.
+fn value(property: &Value, frame: f64) -> Vec<f64> {
+    let value = &property["k"];
+    match keyframes(value).and_then(|keyframes| segment(keyframes, frame)) {
+        Some((from, to, t)) => {
+            let from = numbers(from);
+            let to = numbers(to);
+            if to.len() != from.len() {
+                return from;
+            }
+            from.iter().zip(&to).map(|(a, b)| a + (b - a) * t).collect()
+        }
+        None => numbers(value),
+    }
+}
+
+fn scalar(property: &Value, frame: f64, default: f64) -> f64 {
+    value(property, frame).first().copied().unwrap_or(default)
+}
+
+fn pair(values: Vec<f64>, default: f64) -> [f64; 2] {
+    [
+        values.first().copied().unwrap_or(default),
+        values.get(1).copied().unwrap_or(default),
+    ]
+}
+
+fn keyframes(value: &Value) -> Option<&[Value]> {
+    let keyframes = array(value)?;
+    keyframes
+        .first()
+        .is_some_and(|first| first.get("t").is_some())
+        .then_some(keyframes)
+}
+
+/// Finds the keyframe values either side of `frame` and how far between them
+/// it is, after easing.
+fn segment(keyframes: &[Value], frame: f64) -> Option<(&Value, &Value, f64)> {
+    let time = |keyframe: &Value| keyframe["t"].as_f64().unwrap_or(0.0);
+    let first = keyframes.first()?;
+    if frame <= time(first) {
+        return Some((&first["s"], &first["s"], 0.0));
+    }
+
+    for window in keyframes.windows(2) {
+        let (keyframe, next) = (&window[0], &window[1]);
+        let (start, end) = (time(keyframe), time(next));
+        if frame >= end {
+            continue;
+        }
+        let from = &keyframe["s"];
+        // Older files give each keyframe's end value, newer ones use the next start
+        let to = if keyframe.get("e").is_some() {
+            &keyframe["e"]
+        } else {
+            &next["s"]
+        };
+        if keyframe["h"].as_i64() == Some(1) || end <= start {
+            return Some((from, from, 0.0));
+        }
+        let progress = (frame - start) / (end - start);
+        return Some((from, to, ease(&keyframe["o"], &keyframe["i"], progress)));
+    }
+
+    // Past the last keyframe, which may only mark where the one before it ended
+    let last = keyframes.last()?;
+    if last.get("s").is_some() {
+        return Some((&last["s"], &last["s"], 0.0));
+    }
+    let previous = keyframes.get(keyframes.len().checked_sub(2)?)?;
+    let value = if previous.get("e").is_some() {
+        &previous["e"]
+    } else {
+        &previous["s"]
+    };
+    Some((value, value, 0.0))
+}
+
+/// Applies a keyframe's cubic-bezier easing curve to linear progress `t`.
+fn ease(out_tangent: &Value, in_tangent: &Value, t: f64) -> f64 {
+    let first = |value: &Value, default: f64| numbers(value).first().copied().unwrap_or(default);
+    let (x1, y1) = (first(&out_tangent["x"], 0.0), first(&out_tangent["y"], 0.0));
+    let (x2, y2) = (first(&in_tangent["x"], 1.0), first(&in_tangent["y"], 1.0));
+    let bezier = |a: f64, b: f64, u: f64| {
+        3.0 * (1.0 - u).powi(2) * u * a + 3.0 * (1.0 - u) * u * u * b + u.powi(3)
+    };
+
+    // The curve's x is monotonic, so bisect for the parameter that gives `t`
+    let (mut low, mut high) = (0.0, 1.0);
+    for _ in 0..24 {
+        let middle = (low + high) / 2.0;
+        if bezier(x1, x2, middle) < t {
+            low = middle;
+        } else {
+            high = middle;
+        }
+    }
+    bezier(y1, y2, (low + high) / 2.0)
+}
+
+fn numbers(value: &Value) -> Vec<f64> {
+    match value {
+        Value::Number(number) => number.as_f64().into_iter().collect(),
+        Value::Array(values) => values.iter().filter_map(Value::as_f64).collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn array(value: &Value) -> Option<&[Value]> {
+    value.as_array().map(Vec::as_slice)
+}
+
+fn to_rgba(pixmap: &Pixmap) -> Result<RgbaImage, Box<dyn std::error::Error + Send + Sync>> {
+    let data = pixmap
+        .pixels()
+        .iter()
+        .flat_map(|pixel| {
+            let color = pixel.demultiply();
+            [color.red(), color.green(), color.blue(), color.alpha()]
+        })
+        .collect();
+    RgbaImage::from_raw(pixmap.width(), pixmap.height(), data)
+        .ok_or_else(|| "bad Lottie frame".into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn solid() -> Value {
+        json!({ "ty": 1, "ks": {}, "sw": 64, "sh": 64, "sc": "#ff0000" })
+    }
+
+    fn animation(layers: Value, assets: Value) -> Value {
+        json!({ "w": 64, "h": 64, "fr": 30, "ip": 0, "op": 2, "layers": layers, "assets": assets })
+    }
+
+    fn precomp(id: &str) -> Value {
+        json!({ "ty": 0, "ks": {}, "refId": id })
+    }
+
+    #[test]
+    fn renders_solid_layer() {
+        let root = animation(json!([solid()]), json!([]));
+        let frames = Animation::new(&root).unwrap().render(None).unwrap();
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].image.get_pixel(32, 32).0, [255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn refuses_oversized_animations() {
+        let mut root = animation(json!([]), json!([]));
+        root["w"] = json!(100_000);
+        assert!(Animation::new(&root).is_err());
+
+        let mut root = animation(json!([]), json!([]));
+        root["op"] = json!(1_000_000);
+        assert!(Animation::new(&root).is_err());
+    }
+
+    #[test]
+    fn draws_cyclic_precompositions_once() {
+        let mut layers: Vec<Value> = (0..16).map(|_| precomp("a")).collect();
+        layers.push(solid());
+        let root = animation(
+            json!([precomp("a")]),
+            json!([{ "id": "a", "layers": layers }]),
+        );
+        let frames = Animation::new(&root).unwrap().render(None).unwrap();
+        assert_eq!(frames[0].image.get_pixel(32, 32).0, [255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn bounds_nested_precompositions() {
+        // Sixteen levels that each use the next sixteen times
+        let assets: Vec<Value> = (0..16)
+            .map(|level| {
+                let layers: Vec<Value> = if level == 15 {
+                    vec![solid()]
+                } else {
+                    (0..16)
+                        .map(|_| precomp(&format!("{}", level + 1)))
+                        .collect()
+                };
+                json!({ "id": format!("{level}"), "layers": layers })
+            })
+            .collect();
+        let root = animation(json!([precomp("0")]), json!(assets));
+        assert!(Animation::new(&root).unwrap().render(Some(16)).is_ok());
+    }
+
+    #[test]
+    fn clamps_gradient_stop_count() {
+        let item = json!({
+            "g": { "p": u64::MAX, "k": { "k": [0, 1, 0, 0, 1, 0, 0, 1] } },
+            "s": { "k": [0, 0] },
+            "e": { "k": [10, 0] },
+        });
+        assert!(gradient(&item, 0.0).is_some());
+    }
+}
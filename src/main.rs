@@ -1,29 +1,223 @@
+mod animation;
+mod board_store;
+mod cutout;
+mod dbus_service;
+mod frames;
+mod library;
+mod lottie;
 mod main_window;
+mod pack_export;
+mod pack_import;
 mod recent_store;
+mod session_store;
+mod sticker_paintable;
 mod sticker_window;
+mod svg;
+mod thumbnail_cache;
+mod video;
 
 use gtk::prelude::*;
 use gtk::{glib, Application};
 use libadwaita as adw;
 use std::cell::RefCell;
+use std::path::Path;
 use std::rc::Rc;
 
 use recent_store::RecentStore;
+use session_store::{SessionStore, StickerState};
+use sticker_window::StickerHandle;
 
 const APP_ID: &str = "com.github.toasterrepair.Stickerbook";
 
+/// How stickers opened from the command line or a file manager are shown.
+#[derive(Clone, Copy)]
+struct OpenOptions {
+    scale: f32,
+    rotation: i32,
+    add_to_library: bool,
+}
+
+impl Default for OpenOptions {
+    fn default() -> Self {
+        Self {
+            scale: 1.0,
+            rotation: 0,
+            add_to_library: true,
+        }
+    }
+}
+
 fn main() -> glib::ExitCode {
-    let app = Application::builder().application_id(APP_ID).build();
+    let app = Application::builder()
+        .application_id(APP_ID)
+        .flags(gio::ApplicationFlags::HANDLES_OPEN | gio::ApplicationFlags::HANDLES_COMMAND_LINE)
+        .build();
 
-    app.connect_startup(|_| {
-        adw::init().expect("Failed to initialize libadwaita");
-    });
+    app.add_main_option(
+        "scale",
+        glib::Char::from(b's'),
+        glib::OptionFlags::NONE,
+        glib::OptionArg::Double,
+        "Initial scale of the opened stickers (0.2 to 5)",
+        Some("FACTOR"),
+    );
+    app.add_main_option(
+        "rotate",
+        glib::Char::from(b'r'),
+        glib::OptionFlags::NONE,
+        glib::OptionArg::Int,
+        "Initial rotation of the opened stickers in degrees",
+        Some("DEGREES"),
+    );
+    app.add_main_option(
+        "no-library",
+        glib::Char::from(b'n'),
+        glib::OptionFlags::NONE,
+        glib::OptionArg::None,
+        "Show the files without adding them to the library",
+        None,
+    );
 
     let recent_store = Rc::new(RefCell::new(RecentStore::load()));
 
+    // Track sticker windows for the whole app so they can be opened without the main window
+    let child_windows: Rc<RefCell<Vec<StickerHandle>>> = Rc::new(RefCell::new(Vec::new()));
+
+    let recent_store_startup = recent_store.clone();
+    let child_windows_startup = child_windows.clone();
+    app.connect_startup(move |app| {
+        adw::init().expect("Failed to initialize libadwaita");
+
+        // Locked stickers cannot be clicked, so they are unlocked from here
+        let unlock_action = gio::SimpleAction::new("unlock-stickers", None);
+        let child_windows_unlock = child_windows_startup.clone();
+        unlock_action.connect_activate(move |_, _| {
+            let children: Vec<_> = child_windows_unlock.borrow().iter().cloned().collect();
+            for child in children {
+                child.set_locked(false);
+            }
+        });
+        app.add_action(&unlock_action);
+        app.set_accels_for_action("app.unlock-stickers", &["<Control><Shift>l"]);
+
+        // Remote control for scripts and other desktop tools
+        if let Err(err) = dbus_service::register(
+            app,
+            recent_store_startup.clone(),
+            child_windows_startup.clone(),
+        ) {
+            eprintln!("Failed to export D-Bus interface: {err}");
+        }
+
+        // Drop thumbnails of files that changed or have not been shown for a while
+        gio::spawn_blocking(thumbnail_cache::prune);
+
+        // Restore the sticker windows that were open when the app last quit. Startup
+        // only runs in the primary instance, so launching again never reopens them.
+        for state in SessionStore::load().stickers() {
+            if Path::new(&state.path).exists() {
+                open_sticker(app, state.clone(), &child_windows_startup);
+            }
+        }
+    });
+
+    let recent_store_activate = recent_store.clone();
+    let child_windows_activate = child_windows.clone();
     app.connect_activate(move |app| {
-        main_window::create_main_window(app, recent_store.clone());
+        main_window::create_main_window(
+            app,
+            recent_store_activate.clone(),
+            child_windows_activate.clone(),
+        );
+    });
+
+    let recent_store_cmdline = recent_store.clone();
+    let child_windows_cmdline = child_windows.clone();
+    app.connect_command_line(move |app, cmdline| {
+        let options = cmdline.options_dict();
+        let mut open_options = OpenOptions::default();
+        if let Ok(Some(scale)) = options.lookup::<f64>("scale") {
+            open_options.scale = (scale as f32).clamp(0.2, 5.0);
+        }
+        if let Ok(Some(rotation)) = options.lookup::<i32>("rotate") {
+            open_options.rotation = rotation.rem_euclid(360);
+        }
+        open_options.add_to_library = !options.contains("no-library");
+
+        let files: Vec<gio::File> = cmdline
+            .arguments()
+            .iter()
+            .skip(1)
+            .map(|arg| cmdline.create_file_for_arg(arg))
+            .collect();
+
+        if files.is_empty() {
+            app.activate();
+        } else {
+            open_files(
+                app,
+                &files,
+                open_options,
+                &recent_store_cmdline,
+                &child_windows_cmdline,
+            );
+        }
+        glib::ExitCode::SUCCESS.into()
+    });
+
+    // Files opened through D-Bus activation, e.g. a file manager's "Open With"
+    app.connect_open(move |app, files, _| {
+        open_files(
+            app,
+            files,
+            OpenOptions::default(),
+            &recent_store,
+            &child_windows,
+        );
     });
 
     app.run()
 }
+
+/// Adds files to the library (unless told not to) and shows each as a sticker.
+fn open_files(
+    app: &Application,
+    files: &[gio::File],
+    options: OpenOptions,
+    recent_store: &Rc<RefCell<RecentStore>>,
+    child_windows: &Rc<RefCell<Vec<StickerHandle>>>,
+) {
+    for file in files {
+        let Some(path) = file.path() else {
+            eprintln!("Cannot open {}: not a local file", file.uri());
+            continue;
+        };
+        if !path.is_file() {
+            eprintln!("Cannot open {}: no such file", path.display());
+            continue;
+        }
+
+        let path = if options.add_to_library {
+            let stored_path = recent_store.borrow_mut().import(&path);
+            let _ = recent_store.borrow().save();
+            main_window::notify_library_changed(app);
+            stored_path
+        } else {
+            path.to_string_lossy().to_string()
+        };
+
+        let mut state = StickerState::new(path);
+        state.scale = options.scale;
+        state.rotation = options.rotation;
+        open_sticker(app, state, child_windows);
+    }
+}
+
+fn open_sticker(
+    app: &Application,
+    state: StickerState,
+    child_windows: &Rc<RefCell<Vec<StickerHandle>>>,
+) {
+    let child_window = sticker_window::create_sticker_window(app, state, child_windows.clone());
+    child_windows.borrow_mut().push(child_window);
+}
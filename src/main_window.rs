@@ -1,17 +1,51 @@
-use gdk_pixbuf::PixbufAnimation;
 use gtk::prelude::*;
 use gtk::{gdk, glib, Application};
 use libadwaita as adw;
 use libadwaita::prelude::*;
 use std::cell::RefCell;
-use std::path::Path;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
-use std::time::SystemTime;
 
-use crate::recent_store::RecentStore;
-use crate::sticker_window;
+use crate::animation;
+use crate::board_store::BoardStore;
+use crate::cutout;
+use crate::library;
+use crate::lottie;
+use crate::pack_export::{self, ExportSticker, PackFormat};
+use crate::pack_import;
+use crate::recent_store::{RecentStore, SortMode};
+use crate::session_store::{SessionStore, StickerState};
+use crate::sticker_window::{self, StickerHandle};
+use crate::thumbnail_cache;
+use crate::video;
 
-pub fn create_main_window(app: &Application, recent_store: Rc<RefCell<RecentStore>>) {
+const ALL_STICKERS: &str = "All Stickers";
+const THUMBNAIL_SIZE: u32 = 150;
+
+/// State shared by everything that rebuilds the sticker grid.
+#[derive(Clone)]
+struct GridContext {
+    // Paths of the stickers currently shown, backing the grid view
+    model: gtk::StringList,
+    stack: gtk::Stack,
+    status_page: adw::StatusPage,
+    app: Application,
+    recent_store: Rc<RefCell<RecentStore>>,
+    child_windows: Rc<RefCell<Vec<StickerHandle>>>,
+    // Name of the collection being shown, or None for the whole library
+    collection: Rc<RefCell<Option<String>>>,
+    // Text typed into the search bar
+    query: Rc<RefCell<String>>,
+    // Library files found missing by the last `check_missing`, which are hidden
+    missing: Rc<RefCell<HashSet<String>>>,
+}
+
+pub fn create_main_window(
+    app: &Application,
+    recent_store: Rc<RefCell<RecentStore>>,
+    child_windows: Rc<RefCell<Vec<StickerHandle>>>,
+) {
     let window = adw::ApplicationWindow::builder()
         .application(app)
         .title("Stickerbook")
@@ -19,15 +53,17 @@ pub fn create_main_window(app: &Application, recent_store: Rc<RefCell<RecentStor
         .default_height(600)
         .build();
 
-    // Track child windows
-    let child_windows: Rc<RefCell<Vec<gtk::ApplicationWindow>>> = Rc::new(RefCell::new(Vec::new()));
-    let thumbnail_source_ids: Rc<RefCell<Vec<glib::SourceId>>> = Rc::new(RefCell::new(Vec::new()));
-
     let child_windows_close = child_windows.clone();
     window.connect_close_request(move |_| {
         let children: Vec<_> = child_windows_close.borrow().iter().cloned().collect();
+
+        // Remember which stickers were open so they come back on next launch
+        let mut session = SessionStore::default();
+        session.set_stickers(children.iter().map(StickerHandle::snapshot).collect());
+        let _ = session.save();
+
         for child in children {
-            child.close();
+            child.window.close();
         }
         glib::Propagation::Proceed
     });
@@ -52,80 +88,212 @@ pub fn create_main_window(app: &Application, recent_store: Rc<RefCell<RecentStor
 
     headerbar.pack_start(&add_button);
 
+    // Main menu
+    let menu = gio::Menu::new();
+    menu.append(
+        Some("Copy New Stickers Into Library"),
+        Some("win.copy-to-library"),
+    );
+    menu.append(Some("Import Sticker Pack…"), Some("win.import-pack"));
+    menu.append(
+        Some("Import Sticker Pack Folder…"),
+        Some("win.import-pack-folder"),
+    );
+    menu.append(Some("Export Sticker Pack…"), Some("win.export-pack"));
+    menu.append(Some("Unlock All Stickers"), Some("app.unlock-stickers"));
+    let menu_button = gtk::MenuButton::builder()
+        .icon_name("open-menu-symbolic")
+        .tooltip_text("Main menu")
+        .menu_model(&menu)
+        .build();
+    headerbar.pack_end(&menu_button);
+
+    // Toggle for keeping private copies of imported images
+    let copy_action = gio::SimpleAction::new_stateful(
+        "copy-to-library",
+        None,
+        &recent_store.borrow().copy_to_library().to_variant(),
+    );
+    let recent_store_copy = recent_store.clone();
+    copy_action.connect_activate(move |action, _| {
+        let enabled = !action
+            .state()
+            .and_then(|state| state.get::<bool>())
+            .unwrap_or(false);
+        action.set_state(&enabled.to_variant());
+        recent_store_copy.borrow_mut().set_copy_to_library(enabled);
+        let _ = recent_store_copy.borrow().save();
+    });
+    window.add_action(&copy_action);
+
     // Create toolbar view
     let toolbar_view = adw::ToolbarView::new();
     toolbar_view.add_top_bar(&headerbar);
 
     // Create scrolled window for recent items
     let scrolled = gtk::ScrolledWindow::builder()
-        .hscrollbar_policy(gtk::PolicyType::Automatic)
-        .vscrollbar_policy(gtk::PolicyType::Never)
+        .hscrollbar_policy(gtk::PolicyType::Never)
+        .vscrollbar_policy(gtk::PolicyType::Automatic)
         .vexpand(true)
         .build();
 
-    // Create grid for recent items (supports multiple rows)
-    let recent_grid = gtk::Grid::builder()
-        .row_spacing(12)
-        .column_spacing(12)
-        .row_homogeneous(true)
-        .column_homogeneous(true)
-        .margin_start(12)
-        .margin_end(12)
-        .margin_top(12)
-        .margin_bottom(12)
+    // Empty state, shown instead of the grid when nothing matches
+    let status_page = adw::StatusPage::builder()
         .vexpand(true)
-        .valign(gtk::Align::Fill)
+        .hexpand(true)
         .build();
 
-    scrolled.set_child(Some(&recent_grid));
-    toolbar_view.set_content(Some(&scrolled));
+    let stack = gtk::Stack::new();
+    stack.add_named(&scrolled, Some("grid"));
+    stack.add_named(&status_page, Some("empty"));
+    toolbar_view.set_content(Some(&stack));
 
-    // Track number of rows to display based on window height
-    let max_rows = Rc::new(RefCell::new(2));
+    let ctx = GridContext {
+        model: gtk::StringList::new(&[]),
+        stack,
+        status_page,
+        app: app.clone(),
+        recent_store: recent_store.clone(),
+        child_windows: child_windows.clone(),
+        collection: Rc::new(RefCell::new(None)),
+        query: Rc::new(RefCell::new(String::new())),
+        missing: Rc::new(RefCell::new(HashSet::new())),
+    };
 
-    // Load and display recent items
-    refresh_recent_items(
-        &recent_grid,
-        app,
-        recent_store.clone(),
-        child_windows.clone(),
-        thumbnail_source_ids.clone(),
-        *max_rows.borrow(),
-    );
+    // Grid of thumbnails; only the visible ones are ever built or decoded
+    let grid_view = create_grid_view(&ctx);
+    scrolled.set_child(Some(&grid_view));
 
-    // Set up window resize handler to adapt row count
-    let max_rows_resize = max_rows.clone();
-    let recent_grid_resize = recent_grid.clone();
-    let recent_store_resize = recent_store.clone();
-    let app_resize = app.clone();
-    let child_windows_resize = child_windows.clone();
-    let thumbnail_source_ids_resize = thumbnail_source_ids.clone();
-
-    window.connect_default_height_notify(move |win| {
-        let height = win.default_height();
-        let new_max_rows = if height < 400 { 1 } else { 2 };
-        let current_max_rows = *max_rows_resize.borrow();
-
-        if new_max_rows != current_max_rows {
-            *max_rows_resize.borrow_mut() = new_max_rows;
-            refresh_recent_items(
-                &recent_grid_resize,
-                &app_resize,
-                recent_store_resize.clone(),
-                child_windows_resize.clone(),
-                thumbnail_source_ids_resize.clone(),
-                new_max_rows,
-            );
+    // Drag and drop import
+    ctx.stack.add_controller(create_drop_target(&ctx));
+
+    // Ctrl+V pastes images and files as new stickers
+    let paste_action = gio::SimpleAction::new("paste", None);
+    let ctx_paste = ctx.clone();
+    let window_paste = window.clone();
+    paste_action.connect_activate(move |_, _| {
+        // Let text fields such as the search bar handle their own pasting
+        if let Some(focus) = GtkWindowExt::focus(&window_paste)
+            && focus.is::<gtk::Text>()
+        {
+            let _ = focus.activate_action("clipboard.paste", None);
+            return;
+        }
+        paste_from_clipboard(&ctx_paste, &window_paste.clipboard());
+    });
+    window.add_action(&paste_action);
+    app.set_accels_for_action("win.paste", &["<Control>v"]);
+
+    // Search bar, filtering as you type anywhere in the window
+    let search_entry = gtk::SearchEntry::builder()
+        .placeholder_text("Search stickers")
+        .width_chars(30)
+        .build();
+    search_entry.set_key_capture_widget(Some(&window));
+    headerbar.set_title_widget(Some(&search_entry));
+
+    let ctx_search = ctx.clone();
+    search_entry.connect_search_changed(move |entry| {
+        *ctx_search.query.borrow_mut() = entry.text().to_string();
+        refresh_recent_items(&ctx_search);
+    });
+
+    // Ctrl+F focuses the search bar
+    let search_action = gio::SimpleAction::new("search", None);
+    let search_entry_focus = search_entry.clone();
+    search_action.connect_activate(move |_, _| {
+        search_entry_focus.grab_focus();
+    });
+    window.add_action(&search_action);
+    app.set_accels_for_action("win.search", &["<Control>f"]);
+
+    // Sort order
+    let sort_labels: Vec<&str> = SortMode::ALL.iter().map(|mode| mode.label()).collect();
+    let sort_dropdown = gtk::DropDown::from_strings(&sort_labels);
+    sort_dropdown.set_tooltip_text(Some("Sort by"));
+    let current_sort = recent_store.borrow().sort_mode();
+    if let Some(index) = SortMode::ALL.iter().position(|mode| *mode == current_sort) {
+        sort_dropdown.set_selected(index as u32);
+    }
+
+    let ctx_sort = ctx.clone();
+    sort_dropdown.connect_selected_notify(move |dropdown| {
+        let Some(mode) = SortMode::ALL.get(dropdown.selected() as usize) else {
+            return;
+        };
+        ctx_sort.recent_store.borrow_mut().set_sort_mode(*mode);
+        let _ = ctx_sort.recent_store.borrow().save();
+        refresh_recent_items(&ctx_sort);
+    });
+    headerbar.pack_end(&sort_dropdown);
+
+    // Collection switcher
+    let collection_model = gtk::StringList::new(&[]);
+    let collection_dropdown = gtk::DropDown::builder()
+        .model(&collection_model)
+        .tooltip_text("Collection")
+        .build();
+    refresh_collection_model(&ctx, &collection_model, &collection_dropdown);
+
+    let ctx_switch = ctx.clone();
+    let collection_model_switch = collection_model.clone();
+    collection_dropdown.connect_selected_notify(move |dropdown| {
+        let selected = dropdown.selected();
+        let collection = if selected == 0 || selected == gtk::INVALID_LIST_POSITION {
+            None
+        } else {
+            collection_model_switch
+                .string(selected)
+                .map(|name| name.to_string())
+        };
+        if *ctx_switch.collection.borrow() != collection {
+            *ctx_switch.collection.borrow_mut() = collection;
+            refresh_recent_items(&ctx_switch);
         }
     });
 
+    headerbar.pack_start(&collection_dropdown);
+    headerbar.pack_start(&create_collection_menu(
+        &ctx,
+        &collection_model,
+        &collection_dropdown,
+    ));
+    headerbar.pack_start(&create_board_menu(&ctx));
+
+    // Sticker packs from other apps, either zipped or unpacked into a folder
+    for (name, folder) in [("import-pack", false), ("import-pack-folder", true)] {
+        let action = gio::SimpleAction::new(name, None);
+        let ctx_import = ctx.clone();
+        let model_import = collection_model.clone();
+        let dropdown_import = collection_dropdown.clone();
+        let window_import = window.clone();
+        action.connect_activate(move |_, _| {
+            choose_pack(
+                &ctx_import,
+                &model_import,
+                &dropdown_import,
+                window_import.upcast_ref(),
+                folder,
+            );
+        });
+        window.add_action(&action);
+    }
+
+    // Converts the stickers being shown into a pack for a chat app
+    let export_action = gio::SimpleAction::new("export-pack", None);
+    let ctx_export = ctx.clone();
+    let window_export = window.clone();
+    export_action.connect_activate(move |_, _| {
+        show_export_dialog(&ctx_export, window_export.upcast_ref());
+    });
+    window.add_action(&export_action);
+
+    // Load and display recent items
+    refresh_recent_items(&ctx);
+    check_missing(&ctx);
+
     // Set up file chooser
-    let recent_store_clone = recent_store.clone();
-    let app_clone = app.clone();
-    let recent_grid_clone = recent_grid.clone();
-    let child_windows_clone = child_windows.clone();
-    let max_rows_clone = max_rows.clone();
-    let thumbnail_source_ids_clone = thumbnail_source_ids.clone();
+    let ctx_add = ctx.clone();
     add_button.connect_clicked(move |button| {
         let dialog = gtk::FileDialog::builder()
             .title("Select Image or GIF")
@@ -134,6 +302,8 @@ pub fn create_main_window(app: &Application, recent_store: Rc<RefCell<RecentStor
 
         let filter = gtk::FileFilter::new();
         filter.add_mime_type("image/*");
+        filter.add_suffix("tgs");
+        filter.add_mime_type("video/webm");
         filter.set_name(Some("Images"));
 
         let filters = gio::ListStore::new::<gtk::FileFilter>();
@@ -144,31 +314,16 @@ pub fn create_main_window(app: &Application, recent_store: Rc<RefCell<RecentStor
             .root()
             .and_then(|root| root.downcast::<gtk::Window>().ok());
 
-        let recent_store = recent_store_clone.clone();
-        let app = app_clone.clone();
-        let recent_grid = recent_grid_clone.clone();
-        let child_windows = child_windows_clone.clone();
-        let max_rows = max_rows_clone.clone();
-        let thumbnail_source_ids = thumbnail_source_ids_clone.clone();
-
+        let ctx = ctx_add.clone();
         dialog.open(
             window.as_ref(),
             gtk::gio::Cancellable::NONE,
             move |result| {
-                if let Ok(file) = result {
-                    if let Some(path) = file.path() {
-                        let path_str = path.to_string_lossy().to_string();
-                        recent_store.borrow_mut().add(path_str);
-                        let _ = recent_store.borrow().save();
-                        refresh_recent_items(
-                            &recent_grid,
-                            &app,
-                            recent_store.clone(),
-                            child_windows.clone(),
-                            thumbnail_source_ids.clone(),
-                            *max_rows.borrow(),
-                        );
-                    }
+                if let Ok(file) = result
+                    && let Some(path) = file.path()
+                {
+                    add_sticker(&ctx, &path);
+                    refresh_recent_items(&ctx);
                 }
             },
         );
@@ -176,113 +331,943 @@ pub fn create_main_window(app: &Application, recent_store: Rc<RefCell<RecentStor
 
     window.set_content(Some(&toolbar_view));
     window.present();
+
+    // Stickers may be added from outside this window (command line, "Open With",
+    // D-Bus); those callers go through `notify_library_changed`
+    let refresh_action = gio::SimpleAction::new("refresh-library", None);
+    let ctx_refresh = ctx.clone();
+    refresh_action.connect_activate(move |_, _| {
+        refresh_recent_items(&ctx_refresh);
+        check_missing(&ctx_refresh);
+    });
+    window.add_action(&refresh_action);
 }
 
-fn refresh_recent_items(
-    container: &gtk::Grid,
-    app: &Application,
-    recent_store: Rc<RefCell<RecentStore>>,
-    child_windows: Rc<RefCell<Vec<gtk::ApplicationWindow>>>,
-    thumbnail_source_ids: Rc<RefCell<Vec<glib::SourceId>>>,
-    max_rows: i32,
+/// Makes every open library window show stickers that were added without it.
+pub fn notify_library_changed(app: &Application) {
+    for window in app.windows() {
+        // Sticker windows have no such action
+        let _ = window.activate_action("win.refresh-library", None);
+    }
+}
+
+/// Registers an image in the store, copying it into the library first when that
+/// option is on, and files it under the collection being viewed.
+fn add_sticker(ctx: &GridContext, path: &Path) {
+    let stored_path = ctx.recent_store.borrow_mut().import(path);
+    add_to_current_collection(ctx, &stored_path);
+}
+
+/// Stores image data that has no backing file in the library and registers it.
+fn add_image_data(ctx: &GridContext, bytes: &[u8], extension: &str, source: Option<String>) {
+    let path = match library::import_bytes(bytes, Some(extension)) {
+        Ok(path) => path.to_string_lossy().to_string(),
+        Err(err) => {
+            eprintln!("Failed to save image into the library: {err}");
+            return;
+        }
+    };
+    {
+        let mut store = ctx.recent_store.borrow_mut();
+        store.add(path.clone());
+        if let Some(source) = source {
+            store.set_source(&path, source);
+        }
+    }
+    add_to_current_collection(ctx, &path);
+}
+
+/// Files a newly added sticker under the collection being viewed and saves.
+fn add_to_current_collection(ctx: &GridContext, path: &str) {
+    ctx.missing.borrow_mut().remove(path);
+    let mut store = ctx.recent_store.borrow_mut();
+    if let Some(collection) = ctx.collection.borrow().as_deref() {
+        store.add_to_collection(collection, path);
+    }
+    let _ = store.save();
+}
+
+/// Adds dropped or pasted files. Local files go through `add_sticker`; remote
+/// URIs are downloaded into the library first.
+fn add_files(ctx: &GridContext, files: Vec<gio::File>) {
+    for file in files {
+        if let Some(path) = file.path() {
+            if is_image_file(&path) {
+                add_sticker(ctx, &path);
+            }
+            continue;
+        }
+
+        let ctx = ctx.clone();
+        glib::spawn_future_local(async move {
+            let uri = file.uri().to_string();
+            match file.load_contents_future().await {
+                Ok((bytes, _)) => {
+                    let extension = file
+                        .basename()
+                        .and_then(|name| name.extension().map(|e| e.to_string_lossy().to_string()))
+                        .unwrap_or_else(|| "png".to_string());
+                    add_image_data(&ctx, &bytes, &extension, Some(uri));
+                    refresh_recent_items(&ctx);
+                }
+                Err(err) => eprintln!("Failed to download {uri}: {err}"),
+            }
+        });
+    }
+    refresh_recent_items(ctx);
+}
+
+/// Adds raw pixels, such as a dragged image from a browser, as a PNG in the library.
+fn add_texture(ctx: &GridContext, texture: &gdk::Texture) {
+    add_image_data(ctx, &texture.save_to_png_bytes(), "png", None);
+    refresh_recent_items(ctx);
+}
+
+fn is_image_file(path: &Path) -> bool {
+    let (content_type, _) = gio::content_type_guess(Some(path), &[]);
+    path.is_file()
+        && (content_type.starts_with("image/") || lottie::is_tgs(path) || video::is_video(path))
+}
+
+/// Parses a plain-text list of URIs, as browsers and chat apps often provide.
+fn uri_list_files(text: &str) -> Vec<gio::File> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter(|line| glib::Uri::peek_scheme(line).is_some())
+        .map(gio::File::for_uri)
+        .collect()
+}
+
+/// Adds whatever is on the clipboard: copied files, image data, or a list of URIs.
+fn paste_from_clipboard(ctx: &GridContext, clipboard: &gdk::Clipboard) {
+    let ctx = ctx.clone();
+    let clipboard = clipboard.clone();
+    glib::spawn_future_local(async move {
+        let formats = clipboard.formats();
+
+        if formats.contains_type(gdk::FileList::static_type()) {
+            if let Ok(value) = clipboard
+                .read_value_future(gdk::FileList::static_type(), glib::Priority::DEFAULT)
+                .await
+                && let Ok(file_list) = value.get::<gdk::FileList>()
+            {
+                add_files(&ctx, file_list.files());
+            }
+        } else if formats.contains_type(gdk::Texture::static_type()) {
+            // Screenshots and chat apps often copy pixels with no file behind them
+            match clipboard.read_texture_future().await {
+                Ok(Some(texture)) => add_texture(&ctx, &texture),
+                Ok(None) => {}
+                Err(err) => eprintln!("Failed to read image from clipboard: {err}"),
+            }
+        } else if let Ok(Some(text)) = clipboard.read_text_future().await {
+            let files = uri_list_files(&text);
+            if !files.is_empty() {
+                add_files(&ctx, files);
+            }
+        }
+    });
+}
+
+/// Accepts files, URIs and image data dropped anywhere on the library.
+fn create_drop_target(ctx: &GridContext) -> gtk::DropTarget {
+    let drop_target = gtk::DropTarget::new(glib::Type::INVALID, gdk::DragAction::COPY);
+    drop_target.set_types(&[
+        gdk::FileList::static_type(),
+        gdk::Texture::static_type(),
+        String::static_type(),
+    ]);
+
+    let ctx = ctx.clone();
+    drop_target.connect_drop(move |_, value, _, _| {
+        if let Ok(file_list) = value.get::<gdk::FileList>() {
+            add_files(&ctx, file_list.files());
+            return true;
+        }
+        if let Ok(texture) = value.get::<gdk::Texture>() {
+            add_texture(&ctx, &texture);
+            return true;
+        }
+        if let Ok(text) = value.get::<String>() {
+            let files = uri_list_files(&text);
+            if !files.is_empty() {
+                add_files(&ctx, files);
+                return true;
+            }
+        }
+        false
+    });
+
+    drop_target
+}
+
+/// Asks for a sticker pack zip, or a folder when `folder` is set, and imports it.
+fn choose_pack(
+    ctx: &GridContext,
+    model: &gtk::StringList,
+    dropdown: &gtk::DropDown,
+    window: &gtk::Window,
+    folder: bool,
 ) {
-    for id in thumbnail_source_ids.borrow_mut().drain(..) {
-        id.remove();
+    let dialog = gtk::FileDialog::builder()
+        .title("Import Sticker Pack")
+        .modal(true)
+        .build();
+
+    let ctx = ctx.clone();
+    let model = model.clone();
+    let dropdown = dropdown.clone();
+    let on_chosen = move |result: Result<gio::File, glib::Error>| {
+        if let Ok(file) = result
+            && let Some(path) = file.path()
+        {
+            import_pack(&ctx, &model, &dropdown, path);
+        }
+    };
+
+    if folder {
+        dialog.select_folder(Some(window), gtk::gio::Cancellable::NONE, on_chosen);
+    } else {
+        let filter = gtk::FileFilter::new();
+        filter.add_suffix("zip");
+        filter.add_suffix("wastickers");
+        filter.set_name(Some("Sticker Packs"));
+
+        let filters = gio::ListStore::new::<gtk::FileFilter>();
+        filters.append(&filter);
+        dialog.set_filters(Some(&filters));
+        dialog.open(Some(window), gtk::gio::Cancellable::NONE, on_chosen);
     }
+}
 
-    while let Some(child) = container.first_child() {
-        container.remove(&child);
+/// Reads a sticker pack off the main thread, then files its stickers under a
+/// new collection named after the pack and switches to it.
+fn import_pack(
+    ctx: &GridContext,
+    model: &gtk::StringList,
+    dropdown: &gtk::DropDown,
+    path: PathBuf,
+) {
+    let ctx = ctx.clone();
+    let model = model.clone();
+    let dropdown = dropdown.clone();
+    glib::spawn_future_local(async move {
+        let display_path = path.display().to_string();
+        let pack = match gio::spawn_blocking(move || pack_import::import(&path)).await {
+            Ok(Ok(pack)) => pack,
+            Ok(Err(err)) => {
+                eprintln!("Failed to import sticker pack {display_path}: {err}");
+                show_error(
+                    &ctx.stack,
+                    "Could Not Import Sticker Pack",
+                    &err.to_string(),
+                );
+                return;
+            }
+            Err(_) => {
+                eprintln!("Sticker pack importer panicked on {display_path}");
+                show_error(
+                    &ctx.stack,
+                    "Could Not Import Sticker Pack",
+                    &format!("Something went wrong while reading {display_path}"),
+                );
+                return;
+            }
+        };
+
+        let name = {
+            let mut store = ctx.recent_store.borrow_mut();
+            let name = store.add_pack_collection(&pack.title, pack.author);
+            for sticker in pack.stickers {
+                let path = sticker.path.to_string_lossy().to_string();
+                store.add(path.clone());
+                store.set_source(&path, sticker.source);
+                if let Some(emoji) = sticker.emoji {
+                    store.set_emoji(&path, emoji);
+                }
+                store.add_to_collection(&name, &path);
+            }
+            let _ = store.save();
+            name
+        };
+        *ctx.collection.borrow_mut() = Some(name);
+        refresh_collection_model(&ctx, &model, &dropdown);
+        refresh_recent_items(&ctx);
+    });
+}
+
+/// Asks for the pack's title, author and target app, then a folder to write it
+/// to, and exports the stickers currently shown.
+fn show_export_dialog(ctx: &GridContext, window: &gtk::Window) {
+    let stickers: Vec<ExportSticker> = {
+        let store = ctx.recent_store.borrow();
+        (0..ctx.model.n_items())
+            .filter_map(|index| ctx.model.string(index))
+            .map(|path| ExportSticker {
+                emoji: store
+                    .items()
+                    .iter()
+                    .find(|item| item.path == path.as_str())
+                    .and_then(|item| item.emoji.clone()),
+                path: PathBuf::from(path.as_str()),
+            })
+            .collect()
+    };
+    if stickers.is_empty() {
+        return;
     }
 
-    let items = recent_store.borrow().items().to_vec();
+    // Default to the details of the pack the collection was imported from
+    let (title, author) = {
+        let store = ctx.recent_store.borrow();
+        let collection = ctx.collection.borrow();
+        let collection = collection
+            .as_deref()
+            .and_then(|name| store.collection(name));
+        (
+            collection
+                .map(|c| c.title.clone().unwrap_or_else(|| c.name.clone()))
+                .unwrap_or_else(|| "Stickerbook".to_string()),
+            collection
+                .and_then(|c| c.author.clone())
+                .unwrap_or_default(),
+        )
+    };
+
+    let form = gtk::Box::new(gtk::Orientation::Vertical, 6);
+    let title_entry = gtk::Entry::builder()
+        .placeholder_text("Pack title")
+        .text(title.as_str())
+        .build();
+    let author_entry = gtk::Entry::builder()
+        .placeholder_text("Author")
+        .text(author.as_str())
+        .build();
+    let format_labels: Vec<&str> = PackFormat::ALL
+        .iter()
+        .map(|format| format.label())
+        .collect();
+    let format_dropdown = gtk::DropDown::from_strings(&format_labels);
+    form.append(&title_entry);
+    form.append(&author_entry);
+    form.append(&format_dropdown);
+
+    let dialog = adw::AlertDialog::builder()
+        .heading("Export Sticker Pack")
+        .body(format!(
+            "{} stickers will be resized and converted to suit the app",
+            stickers.len()
+        ))
+        .extra_child(&form)
+        .build();
+    dialog.add_responses(&[("cancel", "Cancel"), ("export", "Export")]);
+    dialog.set_response_appearance("export", adw::ResponseAppearance::Suggested);
+    dialog.set_default_response(Some("export"));
+
+    // Handed to the export once, when the dialog is answered
+    let stickers = RefCell::new(stickers);
+    let window_response = window.clone();
+    dialog.connect_response(None, move |_, response| {
+        if response != "export" {
+            return;
+        }
+        let Some(format) = PackFormat::ALL
+            .get(format_dropdown.selected() as usize)
+            .copied()
+        else {
+            return;
+        };
+        let title = title_entry.text().trim().to_string();
+        let author = author_entry.text().trim().to_string();
+
+        let stickers = std::mem::take(&mut *stickers.borrow_mut());
+        let window = window_response.clone();
+        let folder_dialog = gtk::FileDialog::builder()
+            .title("Export Sticker Pack To")
+            .modal(true)
+            .build();
+        folder_dialog.select_folder(
+            Some(&window_response),
+            gtk::gio::Cancellable::NONE,
+            move |result| {
+                let Some(dir) = result.ok().and_then(|file| file.path()) else {
+                    return;
+                };
+                glib::spawn_future_local(async move {
+                    let exported = gio::spawn_blocking(move || {
+                        pack_export::export(&title, &author, &stickers, format, &dir)
+                    })
+                    .await;
+                    match exported {
+                        Ok(Ok(exported)) => {
+                            if !exported.skipped.is_empty() {
+                                let skipped: Vec<String> = exported
+                                    .skipped
+                                    .iter()
+                                    .map(|(path, reason)| {
+                                        let name = path.file_name().unwrap_or(path.as_os_str());
+                                        format!("{}: {reason}", name.to_string_lossy())
+                                    })
+                                    .collect();
+                                show_error(
+                                    &window,
+                                    "Some Stickers Were Left Out",
+                                    &skipped.join("\n"),
+                                );
+                            }
+                            // Show the result so it can be dragged straight into the app
+                            gtk::FileLauncher::new(Some(&gio::File::for_path(&exported.path)))
+                                .open_containing_folder(
+                                    gtk::Window::NONE,
+                                    gtk::gio::Cancellable::NONE,
+                                    |_| {},
+                                );
+                        }
+                        Ok(Err(err)) => {
+                            eprintln!("Failed to export sticker pack: {err}");
+                            show_error(&window, "Could Not Export Sticker Pack", &err.to_string());
+                        }
+                        Err(_) => {
+                            eprintln!("Sticker pack exporter panicked");
+                            show_error(
+                                &window,
+                                "Could Not Export Sticker Pack",
+                                "Something went wrong while converting the stickers",
+                            );
+                        }
+                    }
+                });
+            },
+        );
+    });
+    dialog.present(Some(window));
+}
+
+/// Tells the user why something they asked for, such as importing or exporting
+/// a sticker pack, could not be done.
+pub fn show_error(parent: &impl IsA<gtk::Widget>, heading: &str, body: &str) {
+    let dialog = adw::AlertDialog::new(Some(heading), Some(body));
+    dialog.add_response("close", "Close");
+    dialog.present(Some(parent));
+}
+
+/// Rebuilds the collection switcher's entries, keeping the current selection.
+fn refresh_collection_model(ctx: &GridContext, model: &gtk::StringList, dropdown: &gtk::DropDown) {
+    let mut names = vec![ALL_STICKERS.to_string()];
+    names.extend(
+        ctx.recent_store
+            .borrow()
+            .collections()
+            .iter()
+            .map(|collection| collection.name.clone()),
+    );
+    let names: Vec<&str> = names.iter().map(String::as_str).collect();
+    let current = ctx.collection.borrow().clone();
+
+    model.splice(0, model.n_items(), &names);
+
+    let selected = current
+        .and_then(|current| names.iter().skip(1).position(|name| *name == current))
+        .map(|index| index as u32 + 1)
+        .unwrap_or(0);
+    dropdown.set_selected(selected);
+}
 
-    // Show empty state if no stickers
-    if items.is_empty() {
-        let status_page = adw::StatusPage::builder()
-            .title("No Stickers")
-            .description("Click the + button to add a sticker")
-            .icon_name("list-add-symbolic")
-            .vexpand(true)
+/// Builds the headerbar menu for creating, renaming and deleting collections.
+fn create_collection_menu(
+    ctx: &GridContext,
+    model: &gtk::StringList,
+    dropdown: &gtk::DropDown,
+) -> gtk::MenuButton {
+    let popover = gtk::Popover::new();
+    let popover_box = gtk::Box::new(gtk::Orientation::Vertical, 6);
+    popover_box.set_margin_start(6);
+    popover_box.set_margin_end(6);
+    popover_box.set_margin_top(6);
+    popover_box.set_margin_bottom(6);
+
+    let name_entry = gtk::Entry::builder()
+        .placeholder_text("Collection name")
+        .build();
+
+    let buttons_box = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+    buttons_box.set_homogeneous(true);
+
+    let create_button = gtk::Button::with_label("Create");
+    let rename_button = gtk::Button::with_label("Rename");
+    let delete_button = gtk::Button::with_label("Delete");
+    delete_button.add_css_class("destructive-action");
+
+    buttons_box.append(&create_button);
+    buttons_box.append(&rename_button);
+    buttons_box.append(&delete_button);
+
+    popover_box.append(&name_entry);
+    popover_box.append(&buttons_box);
+    popover.set_child(Some(&popover_box));
+
+    // Rename and delete act on the collection currently being viewed
+    let ctx_popup = ctx.clone();
+    let name_entry_popup = name_entry.clone();
+    let rename_button_popup = rename_button.clone();
+    let delete_button_popup = delete_button.clone();
+    popover.connect_show(move |_| {
+        let current = ctx_popup.collection.borrow().clone();
+        rename_button_popup.set_sensitive(current.is_some());
+        delete_button_popup.set_sensitive(current.is_some());
+        name_entry_popup.set_text(current.as_deref().unwrap_or(""));
+    });
+
+    let create = {
+        let ctx = ctx.clone();
+        let model = model.clone();
+        let dropdown = dropdown.clone();
+        let name_entry = name_entry.clone();
+        let popover = popover.clone();
+        move || {
+            let name = name_entry.text().trim().to_string();
+            if !ctx.recent_store.borrow_mut().add_collection(&name) {
+                return;
+            }
+            let _ = ctx.recent_store.borrow().save();
+            *ctx.collection.borrow_mut() = Some(name);
+            refresh_collection_model(&ctx, &model, &dropdown);
+            refresh_recent_items(&ctx);
+            popover.popdown();
+        }
+    };
+    let create = Rc::new(create);
+
+    let create_clicked = create.clone();
+    create_button.connect_clicked(move |_| create_clicked());
+    name_entry.connect_activate(move |_| create());
+
+    let ctx_rename = ctx.clone();
+    let model_rename = model.clone();
+    let dropdown_rename = dropdown.clone();
+    let name_entry_rename = name_entry.clone();
+    let popover_rename = popover.clone();
+    rename_button.connect_clicked(move |_| {
+        let Some(old_name) = ctx_rename.collection.borrow().clone() else {
+            return;
+        };
+        let new_name = name_entry_rename.text().trim().to_string();
+        if !ctx_rename
+            .recent_store
+            .borrow_mut()
+            .rename_collection(&old_name, &new_name)
+        {
+            return;
+        }
+        let _ = ctx_rename.recent_store.borrow().save();
+        *ctx_rename.collection.borrow_mut() = Some(new_name);
+        refresh_collection_model(&ctx_rename, &model_rename, &dropdown_rename);
+        popover_rename.popdown();
+    });
+
+    let ctx_delete = ctx.clone();
+    let model_delete = model.clone();
+    let dropdown_delete = dropdown.clone();
+    let popover_delete = popover.clone();
+    delete_button.connect_clicked(move |_| {
+        let Some(name) = ctx_delete.collection.borrow_mut().take() else {
+            return;
+        };
+        ctx_delete
+            .recent_store
+            .borrow_mut()
+            .remove_collection(&name);
+        let _ = ctx_delete.recent_store.borrow().save();
+        refresh_collection_model(&ctx_delete, &model_delete, &dropdown_delete);
+        refresh_recent_items(&ctx_delete);
+        popover_delete.popdown();
+    });
+
+    gtk::MenuButton::builder()
+        .icon_name("folder-symbolic")
+        .tooltip_text("Manage collections")
+        .popover(&popover)
+        .build()
+}
+
+/// Builds the headerbar menu listing saved boards, with buttons to open, close
+/// and delete each one and to save the open stickers as a board.
+fn create_board_menu(ctx: &GridContext) -> gtk::MenuButton {
+    let board_store = Rc::new(RefCell::new(BoardStore::load()));
+
+    let popover = gtk::Popover::new();
+    let popover_box = gtk::Box::new(gtk::Orientation::Vertical, 6);
+    popover_box.set_margin_start(6);
+    popover_box.set_margin_end(6);
+    popover_box.set_margin_top(6);
+    popover_box.set_margin_bottom(6);
+
+    let board_list = gtk::ListBox::builder()
+        .selection_mode(gtk::SelectionMode::None)
+        .build();
+    board_list.add_css_class("boxed-list");
+    board_list.set_placeholder(Some(&gtk::Label::new(Some("No saved boards"))));
+
+    let name_entry = gtk::Entry::builder().placeholder_text("Board name").build();
+    let save_button = gtk::Button::with_label("Save Open Stickers");
+    save_button.add_css_class("suggested-action");
+
+    popover_box.append(&board_list);
+    popover_box.append(&name_entry);
+    popover_box.append(&save_button);
+    popover.set_child(Some(&popover_box));
+
+    let ctx_popup = ctx.clone();
+    let board_store_popup = board_store.clone();
+    let board_list_popup = board_list.clone();
+    let popover_popup = popover.clone();
+    popover.connect_show(move |_| {
+        fill_board_list(
+            &ctx_popup,
+            &board_store_popup,
+            &board_list_popup,
+            &popover_popup,
+        );
+    });
+
+    let save = {
+        let ctx = ctx.clone();
+        let board_store = board_store.clone();
+        let name_entry = name_entry.clone();
+        let popover = popover.clone();
+        move || {
+            let name = name_entry.text().trim().to_string();
+            let stickers = ctx
+                .child_windows
+                .borrow()
+                .iter()
+                .map(StickerHandle::snapshot)
+                .collect();
+            if !board_store.borrow_mut().set_board(&name, stickers) {
+                return;
+            }
+            let _ = board_store.borrow().save();
+
+            // The open stickers now make up this board and close with it
+            for handle in ctx.child_windows.borrow_mut().iter_mut() {
+                handle.board = Some(name.clone());
+            }
+            name_entry.set_text("");
+            popover.popdown();
+        }
+    };
+    let save = Rc::new(save);
+
+    let save_clicked = save.clone();
+    save_button.connect_clicked(move |_| save_clicked());
+    name_entry.connect_activate(move |_| save());
+
+    gtk::MenuButton::builder()
+        .icon_name("view-grid-symbolic")
+        .tooltip_text("Boards")
+        .popover(&popover)
+        .build()
+}
+
+fn fill_board_list(
+    ctx: &GridContext,
+    board_store: &Rc<RefCell<BoardStore>>,
+    board_list: &gtk::ListBox,
+    popover: &gtk::Popover,
+) {
+    board_list.remove_all();
+
+    for board in board_store.borrow().boards() {
+        let row = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+        row.set_margin_start(6);
+        row.set_margin_end(6);
+        row.set_margin_top(3);
+        row.set_margin_bottom(3);
+
+        let label = gtk::Label::builder()
+            .label(format!("{} ({})", board.name, board.stickers.len()))
+            .xalign(0.0)
             .hexpand(true)
-            .halign(gtk::Align::Center)
-            .valign(gtk::Align::Center)
             .build();
-        container.attach(&status_page, 0, 0, 1, 1);
-        return;
+
+        let open_button = gtk::Button::builder()
+            .icon_name("document-open-symbolic")
+            .tooltip_text("Open Board")
+            .build();
+        let close_button = gtk::Button::builder()
+            .icon_name("window-close-symbolic")
+            .tooltip_text("Close Board")
+            .build();
+        let delete_button = gtk::Button::builder()
+            .icon_name("user-trash-symbolic")
+            .tooltip_text("Delete Board")
+            .build();
+        for button in [&open_button, &close_button, &delete_button] {
+            button.add_css_class("flat");
+        }
+
+        let ctx_open = ctx.clone();
+        let board_store_open = board_store.clone();
+        let popover_open = popover.clone();
+        let name = board.name.clone();
+        open_button.connect_clicked(move |_| {
+            let Some(board) = board_store_open.borrow().board(&name).cloned() else {
+                return;
+            };
+            close_board(&ctx_open, &name);
+            for state in board.stickers {
+                if !Path::new(&state.path).exists() {
+                    continue;
+                }
+                let mut handle = sticker_window::create_sticker_window(
+                    &ctx_open.app,
+                    state,
+                    ctx_open.child_windows.clone(),
+                );
+                handle.board = Some(name.clone());
+                ctx_open.child_windows.borrow_mut().push(handle);
+            }
+            popover_open.popdown();
+        });
+
+        let ctx_close = ctx.clone();
+        let name = board.name.clone();
+        close_button.connect_clicked(move |_| close_board(&ctx_close, &name));
+
+        let ctx_delete = ctx.clone();
+        let board_store_delete = board_store.clone();
+        let board_list_delete = board_list.clone();
+        let popover_delete = popover.clone();
+        let name = board.name.clone();
+        delete_button.connect_clicked(move |_| {
+            board_store_delete.borrow_mut().remove_board(&name);
+            let _ = board_store_delete.borrow().save();
+            fill_board_list(
+                &ctx_delete,
+                &board_store_delete,
+                &board_list_delete,
+                &popover_delete,
+            );
+        });
+
+        row.append(&label);
+        row.append(&open_button);
+        row.append(&close_button);
+        row.append(&delete_button);
+        board_list.append(&row);
     }
+}
 
-    let mut col = 0;
-    let mut row = 0;
+/// Closes every sticker window that was opened from or saved to a board.
+fn close_board(ctx: &GridContext, name: &str) {
+    let children: Vec<_> = ctx
+        .child_windows
+        .borrow()
+        .iter()
+        .filter(|handle| handle.board.as_deref() == Some(name))
+        .cloned()
+        .collect();
+    for child in children {
+        child.window.close();
+    }
+}
 
-    for item in items {
-        if !Path::new(&item.path).exists() {
-            continue;
+/// Builds the right-click popover for editing a sticker's tags and collections.
+fn create_item_popover(ctx: &GridContext, path: &str) -> gtk::Popover {
+    let popover = gtk::Popover::new();
+    let popover_box = gtk::Box::new(gtk::Orientation::Vertical, 6);
+    popover_box.set_margin_start(6);
+    popover_box.set_margin_end(6);
+    popover_box.set_margin_top(6);
+    popover_box.set_margin_bottom(6);
+
+    let store = ctx.recent_store.borrow();
+
+    // Tags
+    let tags_label = gtk::Label::builder()
+        .label("Tags")
+        .halign(gtk::Align::Start)
+        .build();
+    tags_label.add_css_class("heading");
+    popover_box.append(&tags_label);
+
+    let tags_box = gtk::FlowBox::builder()
+        .selection_mode(gtk::SelectionMode::None)
+        .max_children_per_line(4)
+        .build();
+
+    let tags = store
+        .items()
+        .iter()
+        .find(|item| item.path == path)
+        .map(|item| item.tags.clone())
+        .unwrap_or_default();
+
+    for tag in tags {
+        append_tag_chip(ctx, path, &tag, &tags_box);
+    }
+    popover_box.append(&tags_box);
+
+    let tag_entry = gtk::Entry::builder().placeholder_text("Add tag").build();
+
+    let ctx_entry = ctx.clone();
+    let path_entry = path.to_string();
+    let tags_box_entry = tags_box.clone();
+    tag_entry.connect_activate(move |entry| {
+        let tag = entry.text().trim().to_string();
+        if tag.is_empty() {
+            return;
+        }
+        entry.set_text("");
+        let added = ctx_entry
+            .recent_store
+            .borrow_mut()
+            .add_tag(&path_entry, &tag);
+        if added {
+            let _ = ctx_entry.recent_store.borrow().save();
+            append_tag_chip(&ctx_entry, &path_entry, &tag, &tags_box_entry);
+        }
+    });
+    popover_box.append(&tag_entry);
+
+    // Collections
+    if !store.collections().is_empty() {
+        let collections_label = gtk::Label::builder()
+            .label("Collections")
+            .halign(gtk::Align::Start)
+            .margin_top(6)
+            .build();
+        collections_label.add_css_class("heading");
+        popover_box.append(&collections_label);
+    }
+
+    for collection in store.collections() {
+        let check = gtk::CheckButton::builder()
+            .label(&collection.name)
+            .active(collection.paths.iter().any(|p| p == path))
+            .build();
+
+        let ctx_check = ctx.clone();
+        let path_check = path.to_string();
+        let name = collection.name.clone();
+        check.connect_toggled(move |check| {
+            let mut store = ctx_check.recent_store.borrow_mut();
+            if check.is_active() {
+                store.add_to_collection(&name, &path_check);
+            } else {
+                store.remove_from_collection(&name, &path_check);
+            }
+            let _ = store.save();
+        });
+        popover_box.append(&check);
+    }
+
+    // Turns a photo into a sticker by cutting out its background
+    let cutout_button = gtk::Button::builder()
+        .label("Remove Background…")
+        .margin_top(6)
+        .build();
+    cutout_button.add_css_class("flat");
+    let ctx_cutout = ctx.clone();
+    let path_cutout = path.to_string();
+    let popover_cutout = popover.clone();
+    cutout_button.connect_clicked(move |_| {
+        popover_cutout.popdown();
+        // Name the cut-out after the image it came from
+        let source = ctx_cutout
+            .recent_store
+            .borrow()
+            .items()
+            .iter()
+            .find(|item| item.path == path_cutout)
+            .map(|item| item.source.clone().unwrap_or_else(|| item.path.clone()));
+        let ctx = ctx_cutout.clone();
+        cutout::present(&ctx_cutout.stack, Path::new(&path_cutout), move |png| {
+            add_image_data(&ctx, &png, "png", source.clone());
+            refresh_recent_items(&ctx);
+        });
+    });
+    popover_box.append(&cutout_button);
+
+    popover.set_child(Some(&popover_box));
+
+    // Membership changes may remove this sticker from the current view
+    let ctx_closed = ctx.clone();
+    popover.connect_closed(move |popover| {
+        let ctx = ctx_closed.clone();
+        let popover = popover.clone();
+        glib::idle_add_local_once(move || {
+            popover.unparent();
+            refresh_recent_items(&ctx);
+        });
+    });
+
+    popover
+}
+
+/// Adds a clickable tag to the popover's tag list that removes itself when pressed.
+fn append_tag_chip(ctx: &GridContext, path: &str, tag: &str, tags_box: &gtk::FlowBox) {
+    let tag_button = gtk::Button::builder()
+        .label(format!("{tag} ✕"))
+        .tooltip_text("Remove tag")
+        .build();
+    tag_button.add_css_class("pill");
+
+    let ctx_tag = ctx.clone();
+    let path_tag = path.to_string();
+    let tag = tag.to_string();
+    tag_button.connect_clicked(move |button| {
+        ctx_tag
+            .recent_store
+            .borrow_mut()
+            .remove_tag(&path_tag, &tag);
+        let _ = ctx_tag.recent_store.borrow().save();
+        if let Some(child) = button.parent() {
+            child.set_visible(false);
         }
+    });
+    tags_box.insert(&tag_button, -1);
+}
+
+/// Builds the grid view over the context's model. Tiles are recycled as the
+/// grid scrolls, so each one looks up its sticker from the list item it is bound to.
+fn create_grid_view(ctx: &GridContext) -> gtk::GridView {
+    let factory = gtk::SignalListItemFactory::new();
+
+    let ctx_setup = ctx.clone();
+    factory.connect_setup(move |_, list_item| {
+        let list_item = list_item
+            .downcast_ref::<gtk::ListItem>()
+            .expect("Needs to be a ListItem");
 
         let item_overlay = gtk::Overlay::new();
 
         // Create picture for the sticker thumbnail (supports animations)
         let picture = gtk::Picture::builder()
-            .width_request(150)
-            .height_request(150)
+            .width_request(THUMBNAIL_SIZE as i32)
+            .height_request(THUMBNAIL_SIZE as i32)
             .can_shrink(true)
-            .vexpand(true)
-            .hexpand(false)
             .content_fit(gtk::ContentFit::Cover)
             .build();
 
-        // Load thumbnail - supports both static and animated images
-        if let Ok(animation) = PixbufAnimation::from_file(&item.path) {
-            if animation.is_static_image() {
-                // For static images, just set the pixbuf
-                if let Some(pixbuf) = animation.static_image() {
-                    let texture = gdk::Texture::for_pixbuf(&pixbuf);
-                    picture.set_paintable(Some(&texture));
-                }
-            } else {
-                // For animated images, set up frame animation
-                let iter = animation.iter(None);
-                let iter_rc = Rc::new(RefCell::new(iter));
-                let picture_clone = picture.clone();
-
-                // Set initial frame
-                let pixbuf = iter_rc.borrow().pixbuf();
-                let texture = gdk::Texture::for_pixbuf(&pixbuf);
-                picture.set_paintable(Some(&texture));
-
-                let thumbnail_source_ids = thumbnail_source_ids.clone();
-                let id = glib::timeout_add_local(std::time::Duration::from_millis(50), move || {
-                    let iter = iter_rc.borrow_mut();
-                    iter.advance(SystemTime::now());
-                    let pixbuf = iter.pixbuf();
-                    let texture = gdk::Texture::for_pixbuf(&pixbuf);
-                    picture_clone.set_paintable(Some(&texture));
-                    glib::ControlFlow::Continue
-                });
-                thumbnail_source_ids.borrow_mut().push(id);
-            }
-        } else {
-            // Fallback to filename if loading fails
-            picture.set_filename(Some(&item.path));
-        }
+        // Right-click to edit tags and collections
+        let right_click = gtk::GestureClick::new();
+        right_click.set_button(3);
 
-        // Make picture clickable
-        let gesture = gtk::GestureClick::new();
-        let app_clone = app.clone();
-        let path_clone = item.path.clone();
-        let recent_store_click = recent_store.clone();
-        let child_windows_click = child_windows.clone();
-        gesture.connect_released(move |_, _, _, _| {
-            recent_store_click.borrow_mut().add(path_clone.clone());
-            let _ = recent_store_click.borrow().save();
-            let child_window = sticker_window::create_sticker_window(
-                &app_clone,
-                &path_clone,
-                child_windows_click.clone(),
-            );
-            child_windows_click.borrow_mut().push(child_window);
+        let ctx_menu = ctx_setup.clone();
+        let list_item_menu = list_item.downgrade();
+        let overlay_menu = item_overlay.clone();
+        right_click.connect_pressed(move |_, _, x, y| {
+            let Some(path) = list_item_menu.upgrade().and_then(|item| bound_path(&item)) else {
+                return;
+            };
+            let popover = create_item_popover(&ctx_menu, &path);
+            popover.set_parent(&overlay_menu);
+            popover.set_pointing_to(Some(&gdk::Rectangle::new(x as i32, y as i32, 1, 1)));
+            popover.popup();
         });
-        picture.add_controller(gesture);
+        picture.add_controller(right_click);
 
         item_overlay.set_child(Some(&picture));
 
@@ -298,23 +1283,18 @@ fn refresh_recent_items(
         remove_button.add_css_class("osd");
         remove_button.add_css_class("circular");
 
-        let recent_store_remove = recent_store.clone();
-        let container_clone = container.clone();
-        let app_clone = app.clone();
-        let child_windows_remove = child_windows.clone();
-        let thumbnail_source_ids_remove = thumbnail_source_ids.clone();
-        let path_for_remove = item.path.clone();
+        let ctx_remove = ctx_setup.clone();
+        let list_item_remove = list_item.downgrade();
         remove_button.connect_clicked(move |_| {
-            recent_store_remove.borrow_mut().remove(&path_for_remove);
-            let _ = recent_store_remove.borrow().save();
-            refresh_recent_items(
-                &container_clone,
-                &app_clone,
-                recent_store_remove.clone(),
-                child_windows_remove.clone(),
-                thumbnail_source_ids_remove.clone(),
-                max_rows,
-            );
+            let Some(path) = list_item_remove
+                .upgrade()
+                .and_then(|item| bound_path(&item))
+            else {
+                return;
+            };
+            ctx_remove.recent_store.borrow_mut().remove(&path);
+            let _ = ctx_remove.recent_store.borrow().save();
+            refresh_recent_items(&ctx_remove);
         });
 
         item_overlay.add_overlay(&remove_button);
@@ -322,17 +1302,192 @@ fn refresh_recent_items(
         // Add frame for better appearance
         let frame = gtk::Frame::new(None);
         frame.set_child(Some(&item_overlay));
-        frame.set_vexpand(true);
-        frame.set_valign(gtk::Align::Fill);
 
-        // Add to grid with row/column layout
-        container.attach(&frame, col, row, 1, 1);
+        list_item.set_child(Some(&frame));
+    });
+
+    factory.connect_bind(move |_, list_item| {
+        let list_item = list_item
+            .downcast_ref::<gtk::ListItem>()
+            .expect("Needs to be a ListItem");
+        let Some(picture) = thumbnail_picture(list_item) else {
+            return;
+        };
+        picture.set_paintable(gdk::Paintable::NONE);
 
-        // Update position for next item
-        row += 1;
-        if row >= max_rows {
-            row = 0;
-            col += 1;
+        let (Some(path), Some(item)) = (bound_path(list_item), list_item.item()) else {
+            return;
+        };
+
+        // Another tile or a recent bind may still be playing this thumbnail
+        if let Some(paintable) = animation::lookup_shared(&path, Some(THUMBNAIL_SIZE)) {
+            picture.set_paintable(Some(&paintable));
+            return;
         }
+
+        // Load from the thumbnail cache off the main thread, then show the result only if this tile
+        // still displays the same sticker
+        let list_item = list_item.downgrade();
+        glib::spawn_future_local(async move {
+            let decoded = if video::is_video(Path::new(&path)) {
+                Ok(video::thumbnail(Path::new(&path), THUMBNAIL_SIZE).await)
+            } else {
+                let path_decode = path.clone();
+                gio::spawn_blocking(move || {
+                    thumbnail_cache::load(Path::new(&path_decode), THUMBNAIL_SIZE)
+                })
+                .await
+            };
+
+            let Some(list_item) = list_item.upgrade() else {
+                return;
+            };
+            if list_item.item().as_ref() != Some(&item) {
+                return;
+            }
+            match decoded {
+                Ok(Ok(frames)) if !frames.is_empty() => {
+                    let paintable = animation::share(&path, Some(THUMBNAIL_SIZE), &frames);
+                    picture.set_paintable(Some(&paintable));
+                }
+                Ok(Ok(_)) => eprintln!("No frames in thumbnail for {path}"),
+                Ok(Err(err)) => eprintln!("Failed to load thumbnail for {path}: {err}"),
+                Err(_) => eprintln!("Thumbnail decoder panicked on {path}"),
+            }
+        });
+    });
+
+    let grid_view = gtk::GridView::builder()
+        .model(&gtk::NoSelection::new(Some(ctx.model.clone())))
+        .factory(&factory)
+        .max_columns(32)
+        .single_click_activate(true)
+        .build();
+
+    // Open the clicked sticker in its own window
+    let ctx_activate = ctx.clone();
+    grid_view.connect_activate(move |_, position| {
+        let Some(path) = ctx_activate.model.string(position) else {
+            return;
+        };
+        let path = path.to_string();
+        ctx_activate.recent_store.borrow_mut().touch(&path);
+        let _ = ctx_activate.recent_store.borrow().save();
+        let child_window = sticker_window::create_sticker_window(
+            &ctx_activate.app,
+            StickerState::new(path),
+            ctx_activate.child_windows.clone(),
+        );
+        ctx_activate.child_windows.borrow_mut().push(child_window);
+    });
+
+    grid_view
+}
+
+/// Returns the sticker path a grid tile is currently bound to.
+fn bound_path(list_item: &gtk::ListItem) -> Option<String> {
+    list_item
+        .item()
+        .and_downcast::<gtk::StringObject>()
+        .map(|item| item.string().to_string())
+}
+
+fn thumbnail_picture(list_item: &gtk::ListItem) -> Option<gtk::Picture> {
+    list_item
+        .child()
+        .and_downcast::<gtk::Frame>()?
+        .child()
+        .and_downcast::<gtk::Overlay>()?
+        .child()
+        .and_downcast::<gtk::Picture>()
+}
+
+/// Looks for library files that have gone missing, such as those on a drive
+/// that is no longer mounted, off the main thread, and hides them from the grid.
+fn check_missing(ctx: &GridContext) {
+    let paths: Vec<String> = ctx
+        .recent_store
+        .borrow()
+        .items()
+        .iter()
+        .map(|item| item.path.clone())
+        .collect();
+    let ctx = ctx.clone();
+    glib::spawn_future_local(async move {
+        let missing = gio::spawn_blocking(move || {
+            paths
+                .into_iter()
+                .filter(|path| !Path::new(path).exists())
+                .collect::<HashSet<String>>()
+        })
+        .await;
+        if let Ok(missing) = missing
+            && *ctx.missing.borrow() != missing
+        {
+            *ctx.missing.borrow_mut() = missing;
+            refresh_recent_items(&ctx);
+        }
+    });
+}
+
+fn refresh_recent_items(ctx: &GridContext) {
+    let paths: Vec<String> = {
+        let store = ctx.recent_store.borrow();
+        let collection = ctx.collection.borrow();
+        let collection = collection
+            .as_deref()
+            .and_then(|name| store.collection(name));
+        let collection: Option<HashSet<&str>> =
+            collection.map(|c| c.paths.iter().map(String::as_str).collect());
+        let matches = store.matcher(&ctx.query.borrow());
+        let missing = ctx.missing.borrow();
+        store
+            .sorted_items()
+            .into_iter()
+            .filter(|item| {
+                collection
+                    .as_ref()
+                    .is_none_or(|paths| paths.contains(item.path.as_str()))
+            })
+            .filter(|item| !missing.contains(&item.path))
+            .filter(|item| matches(item))
+            .map(|item| item.path.clone())
+            .collect()
+    };
+
+    // Leave the model alone when nothing changed so tiles keep their thumbnails
+    let unchanged = ctx.model.n_items() as usize == paths.len()
+        && paths
+            .iter()
+            .enumerate()
+            .all(|(index, path)| ctx.model.string(index as u32).as_deref() == Some(path.as_str()));
+    if !unchanged {
+        let paths_ref: Vec<&str> = paths.iter().map(String::as_str).collect();
+        ctx.model.splice(0, ctx.model.n_items(), &paths_ref);
+    }
+
+    if !paths.is_empty() {
+        ctx.stack.set_visible_child_name("grid");
+        return;
+    }
+
+    if !ctx.query.borrow().trim().is_empty() {
+        // Show a hint when the search matched nothing
+        ctx.status_page.set_title("No Results");
+        ctx.status_page
+            .set_description(Some("Try a different file name or tag"));
+        ctx.status_page
+            .set_icon_name(Some("system-search-symbolic"));
+    } else {
+        // Show empty state if no stickers
+        let description = if ctx.collection.borrow().is_some() {
+            "Right-click a sticker in All Stickers to add it to this collection"
+        } else {
+            "Click the + button to add a sticker"
+        };
+        ctx.status_page.set_title("No Stickers");
+        ctx.status_page.set_description(Some(description));
+        ctx.status_page.set_icon_name(Some("list-add-symbolic"));
     }
+    ctx.stack.set_visible_child_name("empty");
 }
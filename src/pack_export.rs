@@ -0,0 +1,398 @@
+use image::imageops::{self, FilterType};
+use image::{ImageFormat, RgbaImage};
+use serde_json::json;
+use std::fs::{self, File};
+use std::io::{Cursor, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use zip::write::SimpleFileOptions;
+use zip::ZipWriter;
+
+use crate::frames::{self, Frame};
+use crate::lottie;
+use crate::video;
+
+type ExportResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+// Every app wants stickers 512 pixels on their longest side
+const STICKER_SIZE: u32 = 512;
+const WHATSAPP_TRAY_SIZE: u32 = 96;
+// WhatsApp stops animated stickers after ten seconds
+const WHATSAPP_MAX_DURATION: Duration = Duration::from_secs(10);
+// Signal refuses animated stickers longer than three seconds
+const SIGNAL_MAX_DURATION: Duration = Duration::from_secs(3);
+const TELEGRAM_MAX_TGS_SIZE: usize = 64 * 1024;
+// Every nth frame kept, in turn, until an animated PNG fits its size limit
+const APNG_FRAME_STEPS: [usize; 4] = [1, 2, 3, 4];
+const DEFAULT_EMOJI: &str = "🙂";
+// Lossy WebP qualities tried in turn until a sticker fits its size limit
+const WEBP_QUALITIES: [f32; 8] = [90.0, 80.0, 70.0, 60.0, 50.0, 40.0, 30.0, 20.0];
+
+/// The chat apps a collection can be exported for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PackFormat {
+    Telegram,
+    WhatsApp,
+    Signal,
+}
+
+impl PackFormat {
+    pub const ALL: [PackFormat; 3] = [
+        PackFormat::Telegram,
+        PackFormat::WhatsApp,
+        PackFormat::Signal,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            PackFormat::Telegram => "Telegram",
+            PackFormat::WhatsApp => "WhatsApp",
+            PackFormat::Signal => "Signal",
+        }
+    }
+
+    /// How many stickers one pack may hold.
+    fn sticker_limit(self) -> (usize, usize) {
+        match self {
+            PackFormat::Telegram => (1, 120),
+            PackFormat::WhatsApp => (3, 30),
+            PackFormat::Signal => (1, 200),
+        }
+    }
+
+    /// The largest file each sticker may be, still and animated.
+    fn size_limit(self, animated: bool) -> usize {
+        match (self, animated) {
+            (PackFormat::Telegram, _) => 512 * 1024,
+            (PackFormat::WhatsApp, false) => 100 * 1024,
+            (PackFormat::WhatsApp, true) => 500 * 1024,
+            (PackFormat::Signal, _) => 300 * 1024,
+        }
+    }
+}
+
+pub struct ExportSticker {
+    pub path: PathBuf,
+    pub emoji: Option<String>,
+}
+
+/// A written pack, with the stickers that were left out of it and why.
+pub struct Exported {
+    pub path: PathBuf,
+    pub skipped: Vec<(PathBuf, String)>,
+}
+
+/// Converts stickers to the sizes and formats a chat app accepts and writes
+/// them into `dir` with a manifest. Telegram and Signal packs become a folder
+/// named after the title; WhatsApp packs become a `.wastickers` file. Stickers
+/// the app can't take are left out and listed in the result. Runs off the main
+/// thread.
+pub fn export(
+    title: &str,
+    author: &str,
+    stickers: &[ExportSticker],
+    format: PackFormat,
+    dir: &Path,
+) -> ExportResult<Exported> {
+    let (min, max) = format.sticker_limit();
+    if stickers.len() < min || stickers.len() > max {
+        return Err(format!(
+            "{} packs hold {min} to {max} stickers, not {}",
+            format.label(),
+            stickers.len()
+        )
+        .into());
+    }
+
+    let mut converted = Vec::new();
+    let mut skipped = Vec::new();
+    for sticker in stickers {
+        match convert(&sticker.path, format) {
+            Ok((bytes, extension)) => converted.push((sticker, bytes, extension)),
+            Err(err) => skipped.push((sticker.path.clone(), err.to_string())),
+        }
+    }
+    if converted.len() < min {
+        return Err(match skipped.as_slice() {
+            [(path, reason)] if min == 1 => format!("{}: {reason}", path.display()),
+            _ => format!(
+                "{} packs need at least {min} stickers, but only {} could be converted",
+                format.label(),
+                converted.len()
+            ),
+        }
+        .into());
+    }
+
+    let file_name = pack_file_name(title);
+    let emoji = |sticker: &ExportSticker| {
+        sticker
+            .emoji
+            .clone()
+            .unwrap_or_else(|| DEFAULT_EMOJI.to_string())
+    };
+
+    let path = match format {
+        PackFormat::Telegram => {
+            let pack_dir = dir.join(file_name);
+            fs::create_dir_all(&pack_dir)?;
+            let mut entries = Vec::new();
+            for (index, (sticker, bytes, extension)) in converted.iter().enumerate() {
+                let name = format!("{:02}.{extension}", index + 1);
+                fs::write(pack_dir.join(&name), bytes)?;
+                entries.push(json!({ "file": name, "emoji": emoji(sticker) }));
+            }
+            write_manifest(
+                &pack_dir,
+                json!({ "title": title, "author": author, "stickers": entries }),
+            )?;
+            pack_dir
+        }
+        PackFormat::Signal => {
+            // Laid out like a decrypted Signal pack: stickers named by id
+            let pack_dir = dir.join(file_name);
+            fs::create_dir_all(&pack_dir)?;
+            let mut entries = Vec::new();
+            for (id, (sticker, bytes, extension)) in converted.iter().enumerate() {
+                fs::write(pack_dir.join(format!("{id}.{extension}")), bytes)?;
+                entries.push(json!({ "id": id, "emoji": emoji(sticker) }));
+            }
+            write_manifest(
+                &pack_dir,
+                json!({
+                    "title": title,
+                    "author": author,
+                    "cover": entries[0],
+                    "stickers": entries,
+                }),
+            )?;
+            pack_dir
+        }
+        PackFormat::WhatsApp => {
+            let pack_path = dir.join(format!("{file_name}.wastickers"));
+            let mut zip = ZipWriter::new(File::create(&pack_path)?);
+            let options = SimpleFileOptions::default();
+            zip.start_file("title.txt", options)?;
+            zip.write_all(title.as_bytes())?;
+            zip.start_file("author.txt", options)?;
+            zip.write_all(author.as_bytes())?;
+
+            // The tray icon is made from the first sticker, as converted
+            let cover = image::load_from_memory(&converted[0].1)?.to_rgba8();
+            zip.start_file("tray.png", options)?;
+            zip.write_all(&encode_tray(&cover)?)?;
+            for (index, (_, bytes, extension)) in converted.iter().enumerate() {
+                zip.start_file(format!("{:02}.{extension}", index + 1), options)?;
+                zip.write_all(bytes)?;
+            }
+            zip.finish()?;
+            pack_path
+        }
+    };
+    Ok(Exported { path, skipped })
+}
+
+/// Turns a pack title into a name for its folder or file. A title of only dots
+/// would name the chosen folder or its parent, so it gets the default name.
+fn pack_file_name(title: &str) -> String {
+    match title.trim().replace(['/', '\\'], "_") {
+        name if name.chars().all(|c| c == '.') => "Sticker Pack".to_string(),
+        name => name,
+    }
+}
+
+/// Converts one sticker to a file the app accepts, returning its bytes and
+/// extension, or the reason it can't be part of the pack.
+fn convert(path: &Path, format: PackFormat) -> ExportResult<(Vec<u8>, &'static str)> {
+    if video::is_video(path) {
+        return Err("video stickers can't be converted".into());
+    }
+    // Telegram's animated stickers are already Lottie files
+    if format == PackFormat::Telegram && lottie::is_tgs(path) {
+        let bytes = fs::read(path)?;
+        if bytes.len() > TELEGRAM_MAX_TGS_SIZE {
+            return Err("the animation is too large for Telegram".into());
+        }
+        return Ok((bytes, "tgs"));
+    }
+
+    let frames = frames::decode(path)?;
+    let Some(first) = frames.first() else {
+        return Err("the image has no frames".into());
+    };
+    let converted = match (format, frames.len() > 1) {
+        (PackFormat::Telegram, true) => {
+            return Err("Telegram only takes animations made as Lottie stickers".into());
+        }
+        // Telegram wants one side at exactly 512 pixels, so nothing is padded
+        (PackFormat::Telegram, false) => {
+            encode_still(&fit(&first.image, STICKER_SIZE, false), format, true)
+        }
+        (PackFormat::WhatsApp, true) => {
+            encode_animated(&frames, format)?.map(|bytes| (bytes, "webp"))
+        }
+        (PackFormat::Signal, true) => encode_apng(&frames, format)?.map(|bytes| (bytes, "png")),
+        (_, false) => encode_still(&fit(&first.image, STICKER_SIZE, true), format, false),
+    };
+    converted.ok_or_else(|| {
+        format!(
+            "the sticker is too large for {} even at low quality",
+            format.label()
+        )
+        .into()
+    })
+}
+
+fn write_manifest(dir: &Path, manifest: serde_json::Value) -> ExportResult<()> {
+    fs::write(
+        dir.join("manifest.json"),
+        serde_json::to_string_pretty(&manifest)?,
+    )?;
+    Ok(())
+}
+
+/// Scales an image up or down so its longest side is `size` pixels, then
+/// centres it on a transparent square when `pad` is set.
+fn fit(image: &RgbaImage, size: u32, pad: bool) -> RgbaImage {
+    let (width, height) = image.dimensions();
+    let scale = size as f64 / width.max(height) as f64;
+    let new_width = ((width as f64 * scale).round() as u32).clamp(1, size);
+    let new_height = ((height as f64 * scale).round() as u32).clamp(1, size);
+    let resized = imageops::resize(image, new_width, new_height, FilterType::Lanczos3);
+    if !pad {
+        return resized;
+    }
+    let mut square = RgbaImage::new(size, size);
+    imageops::overlay(
+        &mut square,
+        &resized,
+        ((size - new_width) / 2) as i64,
+        ((size - new_height) / 2) as i64,
+    );
+    square
+}
+
+/// Encodes a still sticker as a PNG when `allow_png` is set and it fits,
+/// otherwise as the best lossy WebP that fits. Returns None if nothing does.
+fn encode_still(
+    image: &RgbaImage,
+    format: PackFormat,
+    allow_png: bool,
+) -> Option<(Vec<u8>, &'static str)> {
+    let limit = format.size_limit(false);
+    if allow_png {
+        let mut png = Cursor::new(Vec::new());
+        if image.write_to(&mut png, ImageFormat::Png).is_ok() && png.get_ref().len() <= limit {
+            return Some((png.into_inner(), "png"));
+        }
+    }
+
+    let encoder = webp::Encoder::from_rgba(image.as_raw(), image.width(), image.height());
+    let lossless = encoder.encode_lossless();
+    if lossless.len() <= limit {
+        return Some((lossless.to_vec(), "webp"));
+    }
+    WEBP_QUALITIES
+        .iter()
+        .map(|&quality| encoder.encode(quality))
+        .find(|webp| webp.len() <= limit)
+        .map(|webp| (webp.to_vec(), "webp"))
+}
+
+/// Encodes every frame as an animated WebP, lowering the quality until it fits.
+fn encode_animated(frames: &[Frame], format: PackFormat) -> ExportResult<Option<Vec<u8>>> {
+    let mut images = Vec::new();
+    let mut timestamp = Duration::ZERO;
+    for frame in frames {
+        if timestamp >= WHATSAPP_MAX_DURATION {
+            break;
+        }
+        images.push((
+            fit(&frame.image, STICKER_SIZE, true),
+            timestamp.as_millis() as i32,
+        ));
+        timestamp += frame.delay;
+    }
+
+    let limit = format.size_limit(true);
+    for quality in WEBP_QUALITIES {
+        let mut config =
+            webp::WebPConfig::new().map_err(|_| "failed to set up the WebP encoder")?;
+        config.quality = quality;
+        let mut encoder = webp::AnimEncoder::new(STICKER_SIZE, STICKER_SIZE, &config);
+        for (image, timestamp) in &images {
+            encoder.add_frame(webp::AnimFrame::from_rgba(
+                image.as_raw(),
+                STICKER_SIZE,
+                STICKER_SIZE,
+                *timestamp,
+            ));
+        }
+        let webp = encoder
+            .try_encode()
+            .map_err(|err| format!("failed to encode animated WebP: {err:?}"))?;
+        if webp.len() <= limit {
+            return Ok(Some(webp.to_vec()));
+        }
+    }
+    Ok(None)
+}
+
+/// Encodes an animation as an animated PNG, which is what Signal takes. PNG is
+/// lossless, so frames are dropped instead until it fits.
+fn encode_apng(frames: &[Frame], format: PackFormat) -> ExportResult<Option<Vec<u8>>> {
+    let mut images = Vec::new();
+    let mut duration = Duration::ZERO;
+    for frame in frames {
+        if duration >= SIGNAL_MAX_DURATION {
+            break;
+        }
+        images.push((fit(&frame.image, STICKER_SIZE, true), frame.delay));
+        duration += frame.delay;
+    }
+
+    let limit = format.size_limit(true);
+    for step in APNG_FRAME_STEPS {
+        let chunks: Vec<_> = images.chunks(step).collect();
+        let mut apng = Vec::new();
+        let mut encoder = png::Encoder::new(&mut apng, STICKER_SIZE, STICKER_SIZE);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.set_animated(chunks.len() as u32, 0)?;
+        let mut writer = encoder.write_header()?;
+        for chunk in chunks {
+            // A kept frame stays up for as long as the frames dropped after it
+            let delay: Duration = chunk.iter().map(|(_, delay)| *delay).sum();
+            writer.set_frame_delay(delay.as_millis().min(u16::MAX as u128) as u16, 1000)?;
+            writer.write_image_data(chunk[0].0.as_raw())?;
+        }
+        writer.finish()?;
+        if apng.len() <= limit {
+            return Ok(Some(apng));
+        }
+    }
+    Ok(None)
+}
+
+/// The small PNG WhatsApp shows in its sticker tray, made from the first sticker.
+fn encode_tray(image: &RgbaImage) -> ExportResult<Vec<u8>> {
+    let image = fit(image, WHATSAPP_TRAY_SIZE, true);
+    let mut png = Cursor::new(Vec::new());
+    image.write_to(&mut png, ImageFormat::Png)?;
+    Ok(png.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pack_file_name_stays_inside_folder() {
+        assert_eq!(pack_file_name(" Cats "), "Cats");
+        assert_eq!(pack_file_name("a/b\\c"), "a_b_c");
+        for title in ["", " ", ".", "..", "..."] {
+            assert_eq!(pack_file_name(title), "Sticker Pack");
+        }
+        assert_eq!(pack_file_name("../x"), ".._x");
+    }
+}
@@ -0,0 +1,530 @@
+use serde_json::Value;
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use zip::ZipArchive;
+
+use crate::library;
+
+type ImportResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+// Packs come from the web, and a small zip can expand to gigabytes, so nothing
+// larger than these is read. Telegram's result.json lists a whole chat export.
+const MAX_STICKER_SIZE: u64 = 10 * 1024 * 1024;
+const MAX_MANIFEST_SIZE: u64 = 64 * 1024 * 1024;
+
+/// A sticker pack whose images have been copied into the library.
+pub struct Pack {
+    pub title: String,
+    pub author: Option<String>,
+    pub stickers: Vec<PackSticker>,
+}
+
+pub struct PackSticker {
+    // The library copy of the image
+    pub path: PathBuf,
+    // Where the image came from, shown as the sticker's file name
+    pub source: String,
+    pub emoji: Option<String>,
+}
+
+/// A sticker found in a pack's listing, before its image has been read.
+struct Entry {
+    name: String,
+    emoji: Option<String>,
+}
+
+/// Reads a sticker pack from a folder or zip file and copies every sticker
+/// into the library. Understands Signal's decrypted pack layout (a
+/// `manifest.proto` or `manifest.json` beside the numbered sticker files),
+/// WhatsApp `.wastickers` files, Telegram Desktop exports (`result.json`) and
+/// plain folders or zips of images. Runs off the main thread.
+pub fn import(path: &Path) -> ImportResult<Pack> {
+    let mut archive = if path.is_dir() {
+        Archive::Folder(path.to_path_buf())
+    } else {
+        Archive::Zip(Box::new(ZipArchive::new(File::open(path)?)?))
+    };
+    let names = archive.names()?;
+    let fallback_title = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_else(|| "Sticker Pack".to_string());
+
+    let (title, author, entries) = if let Some(root) = find_root(&names, "result.json") {
+        read_telegram(&mut archive, &root)?
+    } else if let Some(root) = find_root(&names, "manifest.proto") {
+        let manifest = archive.read(&format!("{root}manifest.proto"), MAX_MANIFEST_SIZE)?;
+        read_signal(&names, &root, parse_signal_manifest(&manifest)?)
+    } else if let Some(root) = find_root(&names, "manifest.json") {
+        let manifest: Value = serde_json::from_slice(
+            &archive.read(&format!("{root}manifest.json"), MAX_MANIFEST_SIZE)?,
+        )?;
+        read_signal(&names, &root, signal_manifest_from_json(&manifest))
+    } else {
+        read_plain(&mut archive, &names)
+    };
+
+    let mut stickers = Vec::new();
+    for entry in entries {
+        // Exports can list files that were never written out
+        if !names.contains(&entry.name) {
+            continue;
+        }
+        let bytes = archive.read(&entry.name, MAX_STICKER_SIZE)?;
+        let extension = Path::new(&entry.name)
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_string())
+            .unwrap_or_else(|| sniff_extension(&bytes).to_string());
+        stickers.push(PackSticker {
+            path: library::import_bytes(&bytes, Some(&extension))?,
+            source: path.join(&entry.name).to_string_lossy().to_string(),
+            emoji: entry.emoji,
+        });
+    }
+    if stickers.is_empty() {
+        return Err("no stickers found in the pack".into());
+    }
+
+    Ok(Pack {
+        title: title.unwrap_or(fallback_title),
+        author,
+        stickers,
+    })
+}
+
+enum Archive {
+    Folder(PathBuf),
+    Zip(Box<ZipArchive<File>>),
+}
+
+impl Archive {
+    /// Paths of every file, relative to the folder or zip and separated by `/`.
+    fn names(&self) -> ImportResult<Vec<String>> {
+        match self {
+            Archive::Folder(dir) => {
+                let mut names = Vec::new();
+                list_folder(dir, "", &mut names)?;
+                Ok(names)
+            }
+            Archive::Zip(zip) => Ok(zip
+                .file_names()
+                .filter(|name| !name.ends_with('/') && !name.starts_with("__MACOSX/"))
+                .map(str::to_string)
+                .collect()),
+        }
+    }
+
+    /// Reads a file, refusing it if it is larger than `limit` bytes. A zip's
+    /// own record of the size is checked first, then the data as it is read,
+    /// since the record may be wrong.
+    fn read(&mut self, name: &str, limit: u64) -> ImportResult<Vec<u8>> {
+        let too_large = || format!("{name} is larger than {} MB", limit / 1024 / 1024);
+        let mut bytes = Vec::new();
+        match self {
+            Archive::Folder(dir) => {
+                File::open(dir.join(name))?
+                    .take(limit + 1)
+                    .read_to_end(&mut bytes)?;
+            }
+            Archive::Zip(zip) => {
+                let file = zip.by_name(name)?;
+                if file.size() > limit {
+                    return Err(too_large().into());
+                }
+                file.take(limit + 1).read_to_end(&mut bytes)?;
+            }
+        }
+        if bytes.len() as u64 > limit {
+            return Err(too_large().into());
+        }
+        Ok(bytes)
+    }
+}
+
+fn list_folder(dir: &Path, prefix: &str, names: &mut Vec<String>) -> std::io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = format!("{prefix}{}", entry.file_name().to_string_lossy());
+        if entry.file_type()?.is_dir() {
+            list_folder(&entry.path(), &format!("{name}/"), names)?;
+        } else {
+            names.push(name);
+        }
+    }
+    Ok(())
+}
+
+/// Finds the shallowest folder holding a file called `file_name`, returned as a
+/// prefix ending in `/` (or empty for the top level).
+fn find_root(names: &[String], file_name: &str) -> Option<String> {
+    names
+        .iter()
+        .filter(|name| name.rsplit('/').next() == Some(file_name))
+        .min_by_key(|name| name.matches('/').count())
+        .map(|name| name[..name.len() - file_name.len()].to_string())
+}
+
+fn is_sticker_name(name: &str) -> bool {
+    let (content_type, _) = gio::content_type_guess(Some(name), &[]);
+    content_type.starts_with("image/") || name.ends_with(".tgs") || name.ends_with(".webm")
+}
+
+/// Picks a file extension for data with no name of its own, such as Signal's
+/// numbered sticker files.
+fn sniff_extension(bytes: &[u8]) -> &'static str {
+    if bytes.starts_with(&[0x1f, 0x8b]) {
+        "tgs"
+    } else if bytes.starts_with(&[0x1a, 0x45, 0xdf, 0xa3]) {
+        "webm"
+    } else {
+        image::guess_format(bytes)
+            .ok()
+            .and_then(|format| format.extensions_str().first().copied())
+            .unwrap_or("png")
+    }
+}
+
+type Listing = (Option<String>, Option<String>, Vec<Entry>);
+
+/// A Telegram Desktop chat export lists every message in `result.json`; stickers
+/// are the messages with a `sticker` media type.
+fn read_telegram(archive: &mut Archive, root: &str) -> ImportResult<Listing> {
+    let result: Value =
+        serde_json::from_slice(&archive.read(&format!("{root}result.json"), MAX_MANIFEST_SIZE)?)?;
+    // A full account export nests each chat under `chats.list`
+    let chats: Vec<&Value> = match result["chats"]["list"].as_array() {
+        Some(list) => list.iter().collect(),
+        None => vec![&result],
+    };
+
+    let mut seen = HashSet::new();
+    let mut entries = Vec::new();
+    for message in chats
+        .iter()
+        .filter_map(|chat| chat["messages"].as_array())
+        .flatten()
+    {
+        if message["media_type"].as_str() != Some("sticker") {
+            continue;
+        }
+        // Files left out of the export are replaced by a note in brackets
+        let Some(file) = message["file"]
+            .as_str()
+            .filter(|file| !file.starts_with('('))
+        else {
+            continue;
+        };
+        if seen.insert(file.to_string()) {
+            entries.push(Entry {
+                name: format!("{root}{file}"),
+                emoji: message["sticker_emoji"].as_str().map(str::to_string),
+            });
+        }
+    }
+
+    let title = match chats.as_slice() {
+        [chat] => chat["name"].as_str().map(str::to_string),
+        _ => None,
+    };
+    Ok((title, None, entries))
+}
+
+#[derive(Default)]
+struct SignalManifest {
+    title: Option<String>,
+    author: Option<String>,
+    // Sticker ids with their emoji
+    stickers: Vec<(u64, Option<String>)>,
+}
+
+/// A decrypted Signal pack is its manifest plus one file per sticker, named by
+/// id, either beside the manifest or in a `full` folder as on Signal's CDN.
+fn read_signal(names: &[String], root: &str, manifest: SignalManifest) -> Listing {
+    let entries = manifest
+        .stickers
+        .into_iter()
+        .filter_map(|(id, emoji)| {
+            let name = names.iter().find(|name| {
+                let Some(rest) = name
+                    .strip_prefix(root)
+                    .map(|rest| rest.strip_prefix("full/").unwrap_or(rest))
+                else {
+                    return false;
+                };
+                let stem = rest.split('.').next().unwrap_or(rest);
+                !rest.contains('/') && stem == id.to_string()
+            })?;
+            Some(Entry {
+                name: name.clone(),
+                emoji,
+            })
+        })
+        .collect();
+    (manifest.title, manifest.author, entries)
+}
+
+/// Reads the `Pack` protobuf message: title (1), author (2), cover (3) and
+/// repeated stickers (4), each with an id (1) and emoji (2).
+fn parse_signal_manifest(bytes: &[u8]) -> ImportResult<SignalManifest> {
+    let mut manifest = SignalManifest::default();
+    for (field, value) in ProtoFields(bytes) {
+        match (field?, value) {
+            (1, ProtoValue::Bytes(title)) => manifest.title = Some(proto_string(title)),
+            (2, ProtoValue::Bytes(author)) => manifest.author = Some(proto_string(author)),
+            (4, ProtoValue::Bytes(sticker)) => {
+                let mut id = 0;
+                let mut emoji = None;
+                for (field, value) in ProtoFields(sticker) {
+                    match (field?, value) {
+                        (1, ProtoValue::Varint(value)) => id = value,
+                        (2, ProtoValue::Bytes(value)) => emoji = Some(proto_string(value)),
+                        _ => {}
+                    }
+                }
+                manifest.stickers.push((id, emoji));
+            }
+            _ => {}
+        }
+    }
+    Ok(manifest)
+}
+
+/// Some tools write the decrypted manifest out as JSON with the same fields.
+fn signal_manifest_from_json(manifest: &Value) -> SignalManifest {
+    let string = |value: &Value| value.as_str().map(str::to_string);
+    SignalManifest {
+        title: string(&manifest["title"]),
+        author: string(&manifest["author"]),
+        stickers: manifest["stickers"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|sticker| Some((sticker["id"].as_u64()?, string(&sticker["emoji"]))))
+            .collect(),
+    }
+}
+
+/// Any other folder or zip is taken as a bag of images. WhatsApp's
+/// `.wastickers` files are zips like this, with `title.txt` and `author.txt`
+/// beside WebP stickers and a PNG tray icon.
+fn read_plain(archive: &mut Archive, names: &[String]) -> Listing {
+    let mut read_text = |file_name: &str| {
+        let root = find_root(names, file_name)?;
+        let bytes = archive
+            .read(&format!("{root}{file_name}"), MAX_STICKER_SIZE)
+            .ok()?;
+        let text = String::from_utf8_lossy(&bytes).trim().to_string();
+        (!text.is_empty()).then_some(text)
+    };
+    let title = read_text("title.txt");
+    let author = read_text("author.txt");
+    let is_whatsapp = title.is_some();
+
+    let mut names: Vec<&String> = names
+        .iter()
+        .filter(|name| {
+            if is_whatsapp {
+                name.ends_with(".webp")
+            } else {
+                is_sticker_name(name)
+            }
+        })
+        .collect();
+    names.sort();
+    let entries = names
+        .into_iter()
+        .map(|name| Entry {
+            name: name.clone(),
+            emoji: None,
+        })
+        .collect();
+    (title, author, entries)
+}
+
+fn proto_string(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes).to_string()
+}
+
+enum ProtoValue<'a> {
+    Varint(u64),
+    Bytes(&'a [u8]),
+    Fixed,
+}
+
+/// Iterates over the fields of an encoded protobuf message, yielding each
+/// field number (or a decoding error) with its value.
+struct ProtoFields<'a>(&'a [u8]);
+
+impl<'a> Iterator for ProtoFields<'a> {
+    type Item = (ImportResult<u64>, ProtoValue<'a>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.0.is_empty() {
+            return None;
+        }
+        let malformed = || Some((Err("malformed pack manifest".into()), ProtoValue::Fixed));
+        let Some(key) = read_varint(&mut self.0) else {
+            self.0 = &[];
+            return malformed();
+        };
+        let value = match key & 7 {
+            0 => read_varint(&mut self.0).map(ProtoValue::Varint),
+            1 | 5 => {
+                let len = if key & 7 == 1 { 8 } else { 4 };
+                self.0.get(len..).map(|rest| {
+                    self.0 = rest;
+                    ProtoValue::Fixed
+                })
+            }
+            2 => read_varint(&mut self.0).and_then(|len| {
+                let len = usize::try_from(len).ok()?;
+                let bytes = self.0.get(..len)?;
+                self.0 = &self.0[len..];
+                Some(ProtoValue::Bytes(bytes))
+            }),
+            _ => None,
+        };
+        match value {
+            Some(value) => Some((Ok(key >> 3), value)),
+            None => {
+                self.0 = &[];
+                malformed()
+            }
+        }
+    }
+}
+
+fn read_varint(bytes: &mut &[u8]) -> Option<u64> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let (&byte, rest) = bytes.split_first()?;
+        *bytes = rest;
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Encodes a length-delimited protobuf field.
+    fn bytes_field(field: u8, bytes: &[u8]) -> Vec<u8> {
+        let mut encoded = vec![field << 3 | 2, bytes.len() as u8];
+        encoded.extend_from_slice(bytes);
+        encoded
+    }
+
+    #[test]
+    fn parses_signal_manifest() {
+        let mut sticker = vec![0x08, 0xac, 0x02];
+        sticker.extend(bytes_field(2, "😺".as_bytes()));
+        let mut manifest = bytes_field(1, b"Cats");
+        manifest.extend(bytes_field(2, b"Ann"));
+        // The cover is skipped
+        manifest.extend(bytes_field(3, &[0x08, 0x00]));
+        manifest.extend(bytes_field(4, &sticker));
+        manifest.extend(bytes_field(4, &[0x08, 0x01]));
+
+        let manifest = parse_signal_manifest(&manifest).unwrap();
+        assert_eq!(manifest.title.as_deref(), Some("Cats"));
+        assert_eq!(manifest.author.as_deref(), Some("Ann"));
+        assert_eq!(
+            manifest.stickers,
+            [(300, Some("😺".to_string())), (1, None)]
+        );
+    }
+
+    #[test]
+    fn rejects_truncated_varint() {
+        assert_eq!(read_varint(&mut &[0x96, 0x01][..]), Some(150));
+        assert_eq!(read_varint(&mut &[0x96][..]), None);
+        assert!(parse_signal_manifest(&[0x08, 0x96]).is_err());
+        // A key cut short as well
+        assert!(parse_signal_manifest(&[0x80]).is_err());
+    }
+
+    #[test]
+    fn rejects_length_past_end() {
+        assert!(parse_signal_manifest(&[0x0a, 0x05, b'C', b'a']).is_err());
+        let mut fields = ProtoFields(&[0x22, 0x10, 0x08]);
+        assert!(fields.next().unwrap().0.is_err());
+        assert!(fields.next().is_none());
+    }
+
+    #[test]
+    fn refuses_oversized_zip_entries() {
+        use std::io::Write;
+        use zip::write::SimpleFileOptions;
+
+        let path = std::env::temp_dir().join(format!("stickerbook-{}.zip", std::process::id()));
+        let mut zip = zip::ZipWriter::new(File::create(&path).unwrap());
+        let options = SimpleFileOptions::default();
+        zip.start_file("small.png", options).unwrap();
+        zip.write_all(&[0; 16]).unwrap();
+        // Zeroes compress to almost nothing, like a zip bomb
+        zip.start_file("large.png", options).unwrap();
+        zip.write_all(&vec![0; MAX_STICKER_SIZE as usize + 1])
+            .unwrap();
+        zip.finish().unwrap();
+
+        let mut archive = Archive::Zip(Box::new(
+            ZipArchive::new(File::open(&path).unwrap()).unwrap(),
+        ));
+        assert_eq!(
+            archive.read("small.png", MAX_STICKER_SIZE).unwrap().len(),
+            16
+        );
+        assert!(archive.read("large.png", MAX_STICKER_SIZE).is_err());
+        assert!(archive.read("small.png", 8).is_err());
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn finds_shallowest_root() {
+        let names = [
+            "pack/full/0.webp".to_string(),
+            "pack/nested/manifest.proto".to_string(),
+            "pack/manifest.proto".to_string(),
+        ];
+        assert_eq!(
+            find_root(&names, "manifest.proto").as_deref(),
+            Some("pack/")
+        );
+        assert_eq!(
+            find_root(&["manifest.json".to_string()], "manifest.json").as_deref(),
+            Some("")
+        );
+        assert_eq!(find_root(&names, "result.json"), None);
+    }
+
+    #[test]
+    fn matches_signal_stickers_by_id() {
+        let names = [
+            "pack/manifest.proto".to_string(),
+            "pack/full/0".to_string(),
+            "pack/10.webp".to_string(),
+            "pack/1.webp".to_string(),
+        ];
+        let manifest = SignalManifest {
+            title: Some("Cats".to_string()),
+            author: None,
+            stickers: vec![(0, Some("😺".to_string())), (1, None), (2, None)],
+        };
+        let (title, _, entries) = read_signal(&names, "pack/", manifest);
+        assert_eq!(title.as_deref(), Some("Cats"));
+        let entries: Vec<(&str, Option<&str>)> = entries
+            .iter()
+            .map(|entry| (entry.name.as_str(), entry.emoji.as_deref()))
+            .collect();
+        assert_eq!(
+            entries,
+            [("pack/full/0", Some("😺")), ("pack/1.webp", None)]
+        );
+    }
+}
@@ -1,35 +1,100 @@
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+
+use crate::library;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RecentItem {
     pub path: String,
-    pub timestamp: u64,
+    // Seconds since the epoch when the sticker was last opened or added
+    #[serde(alias = "timestamp")]
+    pub last_used: u64,
+    // Seconds since the epoch when the sticker was added to the library
+    #[serde(default)]
+    pub added: u64,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    // Where the image was imported from, if it was copied into the library
+    #[serde(default)]
+    pub source: Option<String>,
+    // Emoji the sticker was paired with in the pack it was imported from
+    #[serde(default)]
+    pub emoji: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct RecentStore {
-    items: Vec<RecentItem>,
-    max_items: usize,
+impl RecentItem {
+    /// The file name shown to the user, taken from the original file for library copies.
+    pub fn display_name(&self) -> String {
+        Path::new(self.source.as_deref().unwrap_or(&self.path))
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| self.path.clone())
+    }
 }
 
-impl RecentStore {
-    pub fn new(max_items: usize) -> Self {
-        Self {
-            items: Vec::new(),
-            max_items,
+/// How the library is ordered in the main window.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SortMode {
+    #[default]
+    RecentlyUsed,
+    Name,
+    DateAdded,
+}
+
+impl SortMode {
+    pub const ALL: [SortMode; 3] = [SortMode::RecentlyUsed, SortMode::Name, SortMode::DateAdded];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            SortMode::RecentlyUsed => "Recently Used",
+            SortMode::Name => "Name",
+            SortMode::DateAdded => "Date Added",
         }
     }
+}
 
+/// A named, user-defined group of stickers (an album).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Collection {
+    pub name: String,
+    pub paths: Vec<String>,
+    // Title and author of the sticker pack the collection was imported from
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub author: Option<String>,
+}
+
+/// Every sticker in the library, in the order they were added. Nothing is ever
+/// evicted; "recent" is just one of the orderings offered by `sorted_items`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct RecentStore {
+    items: Vec<RecentItem>,
+    #[serde(default)]
+    sort_mode: SortMode,
+    #[serde(default)]
+    collections: Vec<Collection>,
+    #[serde(default)]
+    copy_to_library: bool,
+}
+
+impl RecentStore {
     pub fn load() -> Self {
         let config_path = Self::config_path();
-        if let Ok(content) = fs::read_to_string(&config_path) {
-            if let Ok(store) = serde_json::from_str(&content) {
-                return store;
+        if let Ok(content) = fs::read_to_string(&config_path)
+            && let Ok(mut store) = serde_json::from_str::<Self>(&content)
+        {
+            // Older stores only tracked when an item was last used
+            for item in &mut store.items {
+                if item.added == 0 {
+                    item.added = item.last_used;
+                }
             }
+            return store;
         }
-        Self::new(50)
+        Self::default()
     }
 
     pub fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
@@ -42,32 +107,253 @@ impl RecentStore {
         Ok(())
     }
 
+    /// Adds a sticker to the library, or marks it as used if it is already there.
     pub fn add(&mut self, path: String) {
-        let timestamp = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
+        let timestamp = Self::now();
+        match self.items.iter_mut().find(|item| item.path == path) {
+            Some(item) => item.last_used = timestamp,
+            None => self.items.push(RecentItem {
+                path,
+                last_used: timestamp,
+                added: timestamp,
+                tags: Vec::new(),
+                source: None,
+                emoji: None,
+            }),
+        }
+    }
 
-        // Remove if already exists
-        self.items.retain(|item| item.path != path);
+    /// Adds an image file, copying it into the managed library first when that
+    /// option is on. Returns the path the sticker is stored under.
+    pub fn import(&mut self, path: &Path) -> String {
+        let stored_path = if self.copy_to_library {
+            match library::import(path) {
+                Ok(copy) => copy,
+                Err(err) => {
+                    eprintln!("Failed to copy {} into the library: {err}", path.display());
+                    path.to_path_buf()
+                }
+            }
+        } else {
+            path.to_path_buf()
+        };
 
-        // Add to front
-        self.items.insert(0, RecentItem { path, timestamp });
+        let path_str = stored_path.to_string_lossy().to_string();
+        self.add(path_str.clone());
+        if stored_path != path {
+            self.set_source(&path_str, path.to_string_lossy().to_string());
+        }
+        path_str
+    }
 
-        // Trim to max_items
-        if self.items.len() > self.max_items {
-            self.items.truncate(self.max_items);
+    /// Marks a sticker as just used, moving it to the front of the recent ordering.
+    pub fn touch(&mut self, path: &str) {
+        let timestamp = Self::now();
+        if let Some(item) = self.items.iter_mut().find(|item| item.path == path) {
+            item.last_used = timestamp;
         }
     }
 
     pub fn remove(&mut self, path: &str) {
         self.items.retain(|item| item.path != path);
+        for collection in &mut self.collections {
+            collection.paths.retain(|p| p != path);
+        }
     }
 
     pub fn items(&self) -> &[RecentItem] {
         &self.items
     }
 
+    /// Returns the library ordered by the current sort mode.
+    pub fn sorted_items(&self) -> Vec<&RecentItem> {
+        let mut items: Vec<&RecentItem> = self.items.iter().collect();
+        match self.sort_mode {
+            SortMode::RecentlyUsed => items.sort_by_key(|item| std::cmp::Reverse(item.last_used)),
+            SortMode::Name => items.sort_by_cached_key(|item| item.display_name().to_lowercase()),
+            SortMode::DateAdded => items.sort_by_key(|item| std::cmp::Reverse(item.added)),
+        }
+        items
+    }
+
+    pub fn sort_mode(&self) -> SortMode {
+        self.sort_mode
+    }
+
+    pub fn set_sort_mode(&mut self, sort_mode: SortMode) {
+        self.sort_mode = sort_mode;
+    }
+
+    pub fn set_source(&mut self, path: &str, source: String) {
+        if let Some(item) = self.items.iter_mut().find(|item| item.path == path) {
+            item.source = Some(source);
+        }
+    }
+
+    /// Records the emoji a sticker pack assigned to a sticker.
+    pub fn set_emoji(&mut self, path: &str, emoji: String) {
+        if let Some(item) = self.items.iter_mut().find(|item| item.path == path) {
+            item.emoji = Some(emoji);
+        }
+    }
+
+    /// Whether newly added stickers are copied into the managed library.
+    pub fn copy_to_library(&self) -> bool {
+        self.copy_to_library
+    }
+
+    pub fn set_copy_to_library(&mut self, copy_to_library: bool) {
+        self.copy_to_library = copy_to_library;
+    }
+
+    /// Tags a sticker, returning false if it already had the tag.
+    pub fn add_tag(&mut self, path: &str, tag: &str) -> bool {
+        let tag = tag.trim();
+        if tag.is_empty() {
+            return false;
+        }
+        if let Some(item) = self.items.iter_mut().find(|item| item.path == path)
+            && !item.tags.iter().any(|t| t == tag)
+        {
+            item.tags.push(tag.to_string());
+            return true;
+        }
+        false
+    }
+
+    pub fn remove_tag(&mut self, path: &str, tag: &str) {
+        if let Some(item) = self.items.iter_mut().find(|item| item.path == path) {
+            item.tags.retain(|t| t != tag);
+        }
+    }
+
+    pub fn collections(&self) -> &[Collection] {
+        &self.collections
+    }
+
+    /// Creates an empty collection. Returns false if the name is blank or taken.
+    pub fn add_collection(&mut self, name: &str) -> bool {
+        let name = name.trim();
+        if name.is_empty() || self.collection(name).is_some() {
+            return false;
+        }
+        self.collections.push(Collection {
+            name: name.to_string(),
+            paths: Vec::new(),
+            title: None,
+            author: None,
+        });
+        true
+    }
+
+    /// Creates a collection for an imported sticker pack, named after its title
+    /// with a number added if that name is taken. Returns the name used.
+    pub fn add_pack_collection(&mut self, title: &str, author: Option<String>) -> String {
+        let title = match title.trim() {
+            "" => "Sticker Pack",
+            title => title,
+        };
+        let mut name = title.to_string();
+        let mut number = 2;
+        while self.collection(&name).is_some() {
+            name = format!("{title} ({number})");
+            number += 1;
+        }
+        self.collections.push(Collection {
+            name: name.clone(),
+            paths: Vec::new(),
+            title: Some(title.to_string()),
+            author,
+        });
+        name
+    }
+
+    pub fn remove_collection(&mut self, name: &str) {
+        self.collections
+            .retain(|collection| collection.name != name);
+    }
+
+    /// Renames a collection. Returns false if the new name is blank or taken.
+    pub fn rename_collection(&mut self, old_name: &str, new_name: &str) -> bool {
+        let new_name = new_name.trim();
+        if new_name.is_empty() || self.collection(new_name).is_some() {
+            return false;
+        }
+        match self.collections.iter_mut().find(|c| c.name == old_name) {
+            Some(collection) => {
+                collection.name = new_name.to_string();
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn add_to_collection(&mut self, name: &str, path: &str) {
+        if let Some(collection) = self.collections.iter_mut().find(|c| c.name == name)
+            && !collection.paths.iter().any(|p| p == path)
+        {
+            collection.paths.push(path.to_string());
+        }
+    }
+
+    pub fn remove_from_collection(&mut self, name: &str, path: &str) {
+        if let Some(collection) = self.collections.iter_mut().find(|c| c.name == name) {
+            collection.paths.retain(|p| p != path);
+        }
+    }
+
+    /// Returns a test for whether every word of the query appears in an item's
+    /// file name, tags, emoji or the names of the collections it belongs to.
+    /// Collection names are gathered once, so the test is cheap to run over the
+    /// whole library.
+    pub fn matcher(&self, query: &str) -> impl Fn(&RecentItem) -> bool + '_ {
+        let words: Vec<String> = query
+            .to_lowercase()
+            .split_whitespace()
+            .map(str::to_string)
+            .collect();
+        let mut collections: HashMap<&str, Vec<String>> = HashMap::new();
+        if !words.is_empty() {
+            for collection in &self.collections {
+                let name = collection.name.to_lowercase();
+                for path in &collection.paths {
+                    collections.entry(path).or_default().push(name.clone());
+                }
+            }
+        }
+
+        move |item| {
+            if words.is_empty() {
+                return true;
+            }
+            let file_name = item.display_name().to_lowercase();
+            let tags: Vec<String> = item.tags.iter().map(|tag| tag.to_lowercase()).collect();
+            let collections = collections
+                .get(item.path.as_str())
+                .map_or(&[][..], Vec::as_slice);
+            words.iter().all(|word| {
+                file_name.contains(word.as_str())
+                    || tags.iter().any(|tag| tag.contains(word.as_str()))
+                    || item
+                        .emoji
+                        .as_deref()
+                        .is_some_and(|emoji| emoji.contains(word.as_str()))
+                    || collections.iter().any(|name| name.contains(word.as_str()))
+            })
+        }
+    }
+
+    pub fn collection(&self, name: &str) -> Option<&Collection> {
+        self.collections.iter().find(|c| c.name == name)
+    }
+
+    fn now() -> u64 {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+    }
+
     fn config_path() -> PathBuf {
         let mut path = dirs::config_dir().unwrap_or_else(|| PathBuf::from("."));
         path.push("stickerbook");
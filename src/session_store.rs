@@ -0,0 +1,156 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+/// Which way an animated sticker runs through its frames.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PlaybackMode {
+    #[default]
+    Loop,
+    Reverse,
+    PingPong,
+}
+
+impl PlaybackMode {
+    pub const ALL: [PlaybackMode; 3] = [
+        PlaybackMode::Loop,
+        PlaybackMode::Reverse,
+        PlaybackMode::PingPong,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            PlaybackMode::Loop => "Loop",
+            PlaybackMode::Reverse => "Reverse",
+            PlaybackMode::PingPong => "Ping-pong",
+        }
+    }
+}
+
+/// Animation playback settings, remembered per sticker window.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Playback {
+    pub paused: bool,
+    // Multiplier applied to every frame's own delay, from 0.25 to 4
+    pub speed: f32,
+    pub mode: PlaybackMode,
+}
+
+impl Default for Playback {
+    fn default() -> Self {
+        Self {
+            paused: false,
+            speed: 1.0,
+            mode: PlaybackMode::Loop,
+        }
+    }
+}
+
+/// The border and shadow that make a sticker look cut out of vinyl.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DieCut {
+    // Outline width as a fraction of the image's shorter side, 0 for none
+    pub outline_width: f32,
+    pub outline_color: [u8; 3],
+    pub shadow: bool,
+}
+
+impl Default for DieCut {
+    fn default() -> Self {
+        Self {
+            outline_width: 0.0,
+            outline_color: [255, 255, 255],
+            shadow: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StickerState {
+    pub path: String,
+    pub scale: f32,
+    // Degrees clockwise, 0 to 359
+    pub rotation: i32,
+    #[serde(default)]
+    pub flip_horizontal: bool,
+    #[serde(default)]
+    pub flip_vertical: bool,
+    pub width: i32,
+    pub height: i32,
+    #[serde(default)]
+    pub playback: Playback,
+    // From 0.1 to 1
+    #[serde(default = "default_opacity")]
+    pub opacity: f32,
+    // Locked stickers let clicks through to whatever is underneath
+    #[serde(default)]
+    pub locked: bool,
+    #[serde(default)]
+    pub die_cut: DieCut,
+}
+
+fn default_opacity() -> f32 {
+    1.0
+}
+
+impl StickerState {
+    pub fn new(path: String) -> Self {
+        Self {
+            path,
+            scale: 1.0,
+            rotation: 0,
+            flip_horizontal: false,
+            flip_vertical: false,
+            width: 0,
+            height: 0,
+            playback: Playback::default(),
+            opacity: default_opacity(),
+            locked: false,
+            die_cut: DieCut::default(),
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SessionStore {
+    stickers: Vec<StickerState>,
+}
+
+impl SessionStore {
+    pub fn load() -> Self {
+        let config_path = Self::config_path();
+        if let Ok(content) = fs::read_to_string(&config_path)
+            && let Ok(store) = serde_json::from_str(&content)
+        {
+            return store;
+        }
+        Self::default()
+    }
+
+    pub fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let config_path = Self::config_path();
+        if let Some(parent) = config_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let content = serde_json::to_string_pretty(self)?;
+        fs::write(config_path, content)?;
+        Ok(())
+    }
+
+    pub fn set_stickers(&mut self, stickers: Vec<StickerState>) {
+        self.stickers = stickers;
+    }
+
+    pub fn stickers(&self) -> &[StickerState] {
+        &self.stickers
+    }
+
+    fn config_path() -> PathBuf {
+        let mut path = dirs::config_dir().unwrap_or_else(|| PathBuf::from("."));
+        path.push("stickerbook");
+        path.push("session.json");
+        path
+    }
+}
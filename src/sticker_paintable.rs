@@ -0,0 +1,267 @@
+use gtk::prelude::*;
+use gtk::subclass::prelude::*;
+use gtk::{gdk, glib, graphene, gsk};
+
+use crate::session_store::DieCut;
+
+// Directions the outline is stamped out in; enough that curves look smooth
+const OUTLINE_DIRECTIONS: usize = 16;
+// Shadow offset and blur, as fractions of the image's shorter side
+const SHADOW_OFFSET: f64 = 0.02;
+const SHADOW_BLUR: f64 = 0.04;
+
+mod imp {
+    use super::*;
+    use std::cell::{Cell, RefCell};
+
+    #[derive(Default)]
+    pub struct StickerPaintable {
+        pub inner: RefCell<Option<gdk::Paintable>>,
+        pub handlers: RefCell<Vec<glib::SignalHandlerId>>,
+        // Degrees clockwise
+        pub rotation: Cell<f64>,
+        pub flip_horizontal: Cell<bool>,
+        pub flip_vertical: Cell<bool>,
+        pub die_cut: Cell<DieCut>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for StickerPaintable {
+        const NAME: &'static str = "StickerbookStickerPaintable";
+        type Type = super::StickerPaintable;
+        type Interfaces = (gdk::Paintable,);
+    }
+
+    impl ObjectImpl for StickerPaintable {
+        fn dispose(&self) {
+            self.obj().set_inner(None);
+        }
+    }
+
+    impl PaintableImpl for StickerPaintable {
+        fn intrinsic_width(&self) -> i32 {
+            self.bounds().0.ceil() as i32
+        }
+
+        fn intrinsic_height(&self) -> i32 {
+            self.bounds().1.ceil() as i32
+        }
+
+        fn snapshot(&self, snapshot: &gdk::Snapshot, width: f64, height: f64) {
+            let Some(inner) = self.inner.borrow().clone() else {
+                return;
+            };
+            let Some(snapshot) = snapshot.downcast_ref::<gtk::Snapshot>() else {
+                return;
+            };
+            let (inner_width, inner_height) = inner_size(&inner);
+            let (bounds_width, bounds_height) = self.bounds();
+            if bounds_width <= 0.0 || bounds_height <= 0.0 {
+                return;
+            }
+
+            // Rotate and mirror around the centre of the image, then fit the rotated
+            // bounding box to the area we were given
+            snapshot.save();
+            snapshot.translate(&graphene::Point::new(
+                (width / 2.0) as f32,
+                (height / 2.0) as f32,
+            ));
+            snapshot.scale(
+                (width / bounds_width) as f32,
+                (height / bounds_height) as f32,
+            );
+
+            // Both effects are drawn from the image's alpha, so they follow its
+            // silhouette through every frame. The shadow is pushed before rotating
+            // so it always falls downwards.
+            let die_cut = self.die_cut.get();
+            let short_side = inner_width.min(inner_height);
+            if die_cut.shadow {
+                snapshot.push_shadow(&[gsk::Shadow::new(
+                    gdk::RGBA::new(0.0, 0.0, 0.0, 0.4),
+                    0.0,
+                    (short_side * SHADOW_OFFSET) as f32,
+                    (short_side * SHADOW_BLUR) as f32,
+                )]);
+            }
+            if die_cut.outline_width > 0.0 {
+                snapshot.push_shadow(&outline_shadows(
+                    &die_cut,
+                    short_side * die_cut.outline_width as f64,
+                ));
+            }
+
+            snapshot.rotate(self.rotation.get() as f32);
+            let mirror = |flip: bool| if flip { -1.0 } else { 1.0 };
+            snapshot.scale(
+                mirror(self.flip_horizontal.get()),
+                mirror(self.flip_vertical.get()),
+            );
+            snapshot.translate(&graphene::Point::new(
+                (-inner_width / 2.0) as f32,
+                (-inner_height / 2.0) as f32,
+            ));
+            inner.snapshot(snapshot, inner_width, inner_height);
+            if die_cut.outline_width > 0.0 {
+                snapshot.pop();
+            }
+            if die_cut.shadow {
+                snapshot.pop();
+            }
+            snapshot.restore();
+        }
+    }
+
+    impl StickerPaintable {
+        fn bounds(&self) -> (f64, f64) {
+            match self.inner.borrow().as_ref() {
+                Some(inner) => {
+                    let (width, height) = inner_size(inner);
+                    sticker_bounds(width, height, self.rotation.get(), &self.die_cut.get())
+                }
+                None => (0.0, 0.0),
+            }
+        }
+    }
+
+    /// Copies of the image in the outline colour, stamped in a ring of
+    /// directions at a few distances up to `width` so thin parts are filled.
+    fn outline_shadows(die_cut: &DieCut, width: f64) -> Vec<gsk::Shadow> {
+        let [red, green, blue] = die_cut.outline_color.map(|channel| channel as f32 / 255.0);
+        let color = gdk::RGBA::new(red, green, blue, 1.0);
+        let mut shadows = Vec::new();
+        for ring in 1..=3 {
+            let distance = width * ring as f64 / 3.0;
+            for direction in 0..OUTLINE_DIRECTIONS {
+                let angle = std::f64::consts::TAU * direction as f64 / OUTLINE_DIRECTIONS as f64;
+                let (sin, cos) = angle.sin_cos();
+                shadows.push(gsk::Shadow::new(
+                    color,
+                    (cos * distance) as f32,
+                    (sin * distance) as f32,
+                    0.0,
+                ));
+            }
+        }
+        shadows
+    }
+
+    fn inner_size(inner: &gdk::Paintable) -> (f64, f64) {
+        (
+            inner.intrinsic_width() as f64,
+            inner.intrinsic_height() as f64,
+        )
+    }
+}
+
+glib::wrapper! {
+    /// Draws another paintable rotated by any angle, optionally mirrored and
+    /// given a die-cut outline and shadow, sized to fit all of it so nothing
+    /// is clipped.
+    pub struct StickerPaintable(ObjectSubclass<imp::StickerPaintable>)
+        @implements gdk::Paintable;
+}
+
+impl StickerPaintable {
+    pub fn new() -> Self {
+        glib::Object::new()
+    }
+
+    pub fn set_inner(&self, inner: Option<&gdk::Paintable>) {
+        let imp = self.imp();
+        if let Some(old) = imp.inner.take() {
+            for handler in imp.handlers.take() {
+                old.disconnect(handler);
+            }
+        }
+
+        if let Some(inner) = inner {
+            let weak = self.downgrade();
+            let contents = inner.connect_invalidate_contents(move |_| {
+                if let Some(paintable) = weak.upgrade() {
+                    paintable.invalidate_contents();
+                }
+            });
+            let weak = self.downgrade();
+            let size = inner.connect_invalidate_size(move |_| {
+                if let Some(paintable) = weak.upgrade() {
+                    paintable.invalidate_size();
+                }
+            });
+            imp.handlers.replace(vec![contents, size]);
+            imp.inner.replace(Some(inner.clone()));
+        }
+        self.invalidate_size();
+        self.invalidate_contents();
+    }
+
+    pub fn set_transform(&self, rotation: f64, flip_horizontal: bool, flip_vertical: bool) {
+        let imp = self.imp();
+        imp.rotation.set(rotation);
+        imp.flip_horizontal.set(flip_horizontal);
+        imp.flip_vertical.set(flip_vertical);
+        self.invalidate_size();
+        self.invalidate_contents();
+    }
+
+    pub fn set_die_cut(&self, die_cut: DieCut) {
+        self.imp().die_cut.set(die_cut);
+        self.invalidate_size();
+        self.invalidate_contents();
+    }
+
+    /// Size drawn for a `width` × `height` image at the current rotation and
+    /// die-cut settings.
+    pub fn bounds(&self, width: f64, height: f64) -> (f64, f64) {
+        let imp = self.imp();
+        sticker_bounds(width, height, imp.rotation.get(), &imp.die_cut.get())
+    }
+
+    /// Draws the current frame at the image's own resolution, effects included,
+    /// and encodes it as a PNG.
+    pub fn render_png(&self, renderer: &gsk::Renderer) -> Option<glib::Bytes> {
+        let width = self.intrinsic_width();
+        let height = self.intrinsic_height();
+        if width <= 0 || height <= 0 {
+            return None;
+        }
+        let snapshot = gtk::Snapshot::new();
+        self.snapshot(&snapshot, width as f64, height as f64);
+        let node = snapshot.to_node()?;
+        let viewport = graphene::Rect::new(0.0, 0.0, width as f32, height as f32);
+        Some(
+            renderer
+                .render_texture(&node, Some(&viewport))
+                .save_to_png_bytes(),
+        )
+    }
+}
+
+impl Default for StickerPaintable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Size of everything drawn for a `width` × `height` image: its rotated
+/// bounding box plus room on every side for the outline and shadow.
+pub fn sticker_bounds(width: f64, height: f64, degrees: f64, die_cut: &DieCut) -> (f64, f64) {
+    let (bounds_width, bounds_height) = rotated_bounds(width, height, degrees);
+    let short_side = width.min(height);
+    let mut padding = short_side * die_cut.outline_width as f64;
+    if die_cut.shadow {
+        padding += short_side * (SHADOW_OFFSET + SHADOW_BLUR);
+    }
+    (bounds_width + padding * 2.0, bounds_height + padding * 2.0)
+}
+
+/// Size of the box that holds a `width` × `height` rectangle rotated by
+/// `degrees`.
+pub fn rotated_bounds(width: f64, height: f64, degrees: f64) -> (f64, f64) {
+    let (sin, cos) = degrees.to_radians().sin_cos();
+    (
+        width * cos.abs() + height * sin.abs(),
+        width * sin.abs() + height * cos.abs(),
+    )
+}
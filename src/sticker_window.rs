@@ -1,15 +1,68 @@
-use gdk_pixbuf::PixbufAnimation;
 use gtk::prelude::*;
 use gtk::{gdk, glib, Application};
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
+use std::path::Path;
 use std::rc::Rc;
-use std::time::SystemTime;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use crate::animation::{self, AnimatedPaintable};
+use crate::frames;
+use crate::session_store::{DieCut, Playback, PlaybackMode, StickerState};
+use crate::sticker_paintable::{sticker_bounds, StickerPaintable};
+use crate::svg::SvgRenderer;
+use crate::video;
+
+/// An open sticker window together with the state that is saved for it.
+#[derive(Clone)]
+pub struct StickerHandle {
+    // Unique for the lifetime of the process, used to address windows over D-Bus
+    pub id: u32,
+    pub window: gtk::ApplicationWindow,
+    pub state: Rc<RefCell<StickerState>>,
+    // Natural size of the image, once it is known; videos only report it after loading
+    pub image_size: Rc<Cell<Option<(i32, i32)>>>,
+    // Board the window was opened from or last saved to
+    pub board: Option<String>,
+}
+
+impl StickerHandle {
+    /// Returns the sticker's state with the window's current size filled in.
+    pub fn snapshot(&self) -> StickerState {
+        let mut state = self.state.borrow().clone();
+        if self.window.width() > 0 && self.window.height() > 0 {
+            state.width = self.window.width();
+            state.height = self.window.height();
+        }
+        state
+    }
+
+    pub fn set_locked(&self, locked: bool) {
+        self.state.borrow_mut().locked = locked;
+        apply_lock(&self.window, locked);
+    }
+
+    pub fn set_scale(&self, scale: f32) {
+        // Resizing the window notifies handlers that read the state, so the
+        // borrow is released first
+        let state = {
+            let mut state = self.state.borrow_mut();
+            state.scale = scale.clamp(0.2, 5.0);
+            state.clone()
+        };
+        if let Some(image_size) = self.image_size.get() {
+            apply_size(&self.window, &state, image_size);
+        }
+    }
+}
 
 pub fn create_sticker_window(
     app: &Application,
-    image_path: &str,
-    child_windows: Rc<RefCell<Vec<gtk::ApplicationWindow>>>,
-) -> gtk::ApplicationWindow {
+    state: StickerState,
+    child_windows: Rc<RefCell<Vec<StickerHandle>>>,
+) -> StickerHandle {
+    let image_path = state.path.clone();
+    let state = Rc::new(RefCell::new(state));
+
     let window = gtk::ApplicationWindow::builder()
         .application(app)
         .default_width(400)
@@ -29,84 +82,89 @@ pub fn create_sticker_window(
     // Set minimum size for the picture widget
     picture.set_size_request(25, 25);
 
-    // Variables to store aspect ratio
-    let mut aspect_ratio = 1.0_f32;
-    let mut has_image = false;
-    let mut image_width = 400;
-    let mut image_height = 400;
-
-    let anim_source_id: Rc<RefCell<Option<glib::SourceId>>> = Rc::new(RefCell::new(None));
-
-    if let Ok(animation) = PixbufAnimation::from_file(image_path) {
-        let pixbuf = if animation.is_static_image() {
-            animation.static_image()
-        } else {
-            Some(animation.iter(None).pixbuf())
-        };
-
-        if let Some(pixbuf) = &pixbuf {
-            let width = pixbuf.width();
-            let height = pixbuf.height();
-            aspect_ratio = width as f32 / height as f32;
-            has_image = true;
-            image_width = width.max(25);
-            image_height = height.max(25);
-
-            window.set_default_size(image_width, image_height);
-        }
-
-        if animation.is_static_image() {
-            if let Some(pixbuf) = pixbuf {
-                let texture = gdk::Texture::for_pixbuf(&pixbuf);
-                picture.set_paintable(Some(&texture));
+    // Natural size of the image, once it has been decoded
+    let image_size: Rc<Cell<Option<(i32, i32)>>> = Rc::new(Cell::new(None));
+
+    // Draws the image rotated and mirrored; the window is sized to its bounding box
+    let sticker = StickerPaintable::new();
+    {
+        let state = state.borrow();
+        sticker.set_transform(
+            state.rotation as f64,
+            state.flip_horizontal,
+            state.flip_vertical,
+        );
+        sticker.set_die_cut(state.die_cut);
+    }
+    picture.set_paintable(Some(&sticker));
+
+    // Videos are streamed; the size is only known once GStreamer has opened them
+    let is_video = video::is_video(Path::new(&image_path));
+    if is_video {
+        let media = video::open(&image_path);
+        sticker.set_inner(Some(media.upcast_ref()));
+
+        let image_size_media = image_size.clone();
+        let window_media = window.downgrade();
+        let state_media = state.clone();
+        media.connect_invalidate_size(move |media| {
+            let (width, height) = (media.intrinsic_width(), media.intrinsic_height());
+            if width <= 0 || height <= 0 || image_size_media.get().is_some() {
+                return;
             }
-        } else {
-            let iter = animation.iter(None);
-            let iter_rc = Rc::new(RefCell::new(iter));
-            let picture_clone = picture.clone();
-
-            let pixbuf = iter_rc.borrow().pixbuf();
-            let texture = gdk::Texture::for_pixbuf(&pixbuf);
-            picture.set_paintable(Some(&texture));
-
-            let source_id_clone = anim_source_id.clone();
-            let id = glib::timeout_add_local(std::time::Duration::from_millis(30), move || {
-                let iter = iter_rc.borrow_mut();
-                iter.advance(SystemTime::now());
-                let pixbuf = iter.pixbuf();
-                let texture = gdk::Texture::for_pixbuf(&pixbuf);
-                picture_clone.set_paintable(Some(&texture));
-                glib::ControlFlow::Continue
-            });
-            *source_id_clone.borrow_mut() = Some(id);
-        }
-    } else {
-        // Fallback to filename if loading fails
-        picture.set_filename(Some(image_path));
+            image_size_media.set(Some((width.max(25), height.max(25))));
+            if let Some(window) = window_media.upgrade() {
+                apply_size(
+                    &window,
+                    &state_media.borrow(),
+                    (width.max(25), height.max(25)),
+                );
+            }
+        });
     }
 
     // Wrap picture in AspectFrame to maintain aspect ratio during resize
     let aspect_frame = gtk::AspectFrame::builder()
-        .ratio(aspect_ratio)
+        .ratio(1.0)
         .obey_child(false)
         .build();
     aspect_frame.set_child(Some(&picture));
 
     // Create popover with controls
     let popover = gtk::Popover::new();
-    let popover_box = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+    let popover_box = gtk::Box::new(gtk::Orientation::Vertical, 6);
     popover_box.set_margin_start(6);
     popover_box.set_margin_end(6);
     popover_box.set_margin_top(6);
     popover_box.set_margin_bottom(6);
-
-    // Store rotation and scale state
-    let rotation_angle = Rc::new(RefCell::new(0));
-    let scale = Rc::new(RefCell::new(1.0_f32));
-    let rotation_angle_clone = rotation_angle.clone();
-    let scale_clone = scale.clone();
-    let picture_clone = picture.clone();
-    let window_clone = window.clone();
+    let button_row = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+
+    // Any-angle rotation; the rotate button, double-click and Shift+scroll all go
+    // through this slider
+    let rotation_scale = gtk::Scale::with_range(gtk::Orientation::Horizontal, 0.0, 359.0, 1.0);
+    rotation_scale.set_tooltip_text(Some("Rotation"));
+    rotation_scale.set_digits(0);
+    rotation_scale.set_value_pos(gtk::PositionType::Right);
+    rotation_scale.set_hexpand(true);
+    rotation_scale.set_value(state.borrow().rotation as f64);
+
+    let state_rotation = state.clone();
+    let window_rotation = window.clone();
+    let sticker_rotation = sticker.clone();
+    let image_size_rotation = image_size.clone();
+    rotation_scale.connect_value_changed(move |scale| {
+        let state = {
+            let mut state = state_rotation.borrow_mut();
+            state.rotation = scale.value().round() as i32;
+            state.clone()
+        };
+        apply_transform(
+            &window_rotation,
+            &sticker_rotation,
+            &state,
+            image_size_rotation.get(),
+        );
+    });
 
     // Rotate button in popover
     let rotate_button = gtk::Button::builder()
@@ -114,39 +172,193 @@ pub fn create_sticker_window(
         .tooltip_text("Rotate 90°")
         .build();
 
+    let state_rotate = state.clone();
+    let rotation_scale_rotate = rotation_scale.clone();
     rotate_button.connect_clicked(move |_| {
-        let mut angle = rotation_angle_clone.borrow_mut();
-        *angle = (*angle + 90) % 360;
-
-        let css_provider = gtk::CssProvider::new();
-        let rotation_css = format!(
-            r#"
-            picture {{
-                transform: rotate({}deg);
-                transition: transform 200ms ease-in-out;
-            }}
-            "#,
-            *angle
-        );
-        css_provider.load_from_string(&rotation_css);
-
-        picture_clone
-            .style_context()
-            .add_provider(&css_provider, gtk::STYLE_PROVIDER_PRIORITY_USER);
-
-        if has_image {
-            let s = scale_clone.borrow();
-            let ratio = if *angle == 90 || *angle == 270 {
-                image_height as f32 / image_width as f32
-            } else {
-                image_width as f32 / image_height as f32
+        let rotation = state_rotate.borrow().rotation;
+        rotation_scale_rotate.set_value(((rotation + 90).rem_euclid(360)) as f64);
+    });
+
+    // Mirror buttons in popover
+    let flip_horizontal_button = gtk::ToggleButton::builder()
+        .icon_name("object-flip-horizontal-symbolic")
+        .tooltip_text("Flip Horizontally")
+        .active(state.borrow().flip_horizontal)
+        .build();
+    let flip_vertical_button = gtk::ToggleButton::builder()
+        .icon_name("object-flip-vertical-symbolic")
+        .tooltip_text("Flip Vertically")
+        .active(state.borrow().flip_vertical)
+        .build();
+
+    for (button, horizontal) in [
+        (&flip_horizontal_button, true),
+        (&flip_vertical_button, false),
+    ] {
+        let state = state.clone();
+        let window = window.clone();
+        let sticker = sticker.clone();
+        let image_size = image_size.clone();
+        button.connect_toggled(move |button| {
+            let state = {
+                let mut state = state.borrow_mut();
+                if horizontal {
+                    state.flip_horizontal = button.is_active();
+                } else {
+                    state.flip_vertical = button.is_active();
+                }
+                state.clone()
+            };
+            apply_transform(&window, &sticker, &state, image_size.get());
+        });
+    }
+
+    // Opacity slider; Ctrl+scroll goes through it too
+    let opacity_scale = gtk::Scale::with_range(gtk::Orientation::Horizontal, 0.1, 1.0, 0.05);
+    opacity_scale.set_tooltip_text(Some("Opacity"));
+    opacity_scale.set_value_pos(gtk::PositionType::Right);
+    opacity_scale.set_format_value_func(|_, value| format!("{:.0}%", value * 100.0));
+    opacity_scale.set_value(state.borrow().opacity as f64);
+    picture.set_opacity(state.borrow().opacity as f64);
+
+    let state_opacity = state.clone();
+    let picture_opacity = picture.clone();
+    opacity_scale.connect_value_changed(move |scale| {
+        state_opacity.borrow_mut().opacity = scale.value() as f32;
+        picture_opacity.set_opacity(scale.value());
+    });
+
+    // Die-cut outline and drop shadow, drawn around the image's silhouette
+    let outline_scale = gtk::Scale::with_range(gtk::Orientation::Horizontal, 0.0, 0.1, 0.005);
+    outline_scale.set_tooltip_text(Some("Outline Width"));
+    outline_scale.set_value_pos(gtk::PositionType::Right);
+    outline_scale.set_format_value_func(|_, value| format!("{:.1}%", value * 100.0));
+    outline_scale.set_value(state.borrow().die_cut.outline_width as f64);
+
+    let [red, green, blue] = state.borrow().die_cut.outline_color;
+    let outline_color_button =
+        gtk::ColorDialogButton::new(Some(gtk::ColorDialog::builder().with_alpha(false).build()));
+    outline_color_button.set_tooltip_text(Some("Outline Color"));
+    outline_color_button.set_rgba(&gdk::RGBA::new(
+        red as f32 / 255.0,
+        green as f32 / 255.0,
+        blue as f32 / 255.0,
+        1.0,
+    ));
+    let shadow_check = gtk::CheckButton::builder()
+        .label("Shadow")
+        .active(state.borrow().die_cut.shadow)
+        .hexpand(true)
+        .build();
+
+    let update_die_cut = {
+        let state = state.clone();
+        let window = window.clone();
+        let sticker = sticker.clone();
+        let image_size = image_size.clone();
+        Rc::new(move |update: &dyn Fn(&mut DieCut)| {
+            let state = {
+                let mut state = state.borrow_mut();
+                update(&mut state.die_cut);
+                state.clone()
             };
-            let w = (image_width as f32 * *s).max(25.0) as i32;
-            let h = (w as f32 / ratio).max(25.0) as i32;
-            window_clone.set_default_size(w, h);
+            sticker.set_die_cut(state.die_cut);
+            if let Some(image_size) = image_size.get() {
+                apply_size(&window, &state, image_size);
+            }
+        })
+    };
+
+    let update_outline = update_die_cut.clone();
+    outline_scale.connect_value_changed(move |scale| {
+        update_outline(&|die_cut| die_cut.outline_width = scale.value() as f32);
+    });
+    let update_color = update_die_cut.clone();
+    outline_color_button.connect_rgba_notify(move |button| {
+        let rgba = button.rgba();
+        let channel = |value: f32| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+        let color = [
+            channel(rgba.red()),
+            channel(rgba.green()),
+            channel(rgba.blue()),
+        ];
+        update_color(&|die_cut| die_cut.outline_color = color);
+    });
+    shadow_check.connect_toggled(move |check| {
+        update_die_cut(&|die_cut| die_cut.shadow = check.is_active());
+    });
+
+    // Saves the current frame, outline and shadow included, as a new PNG
+    let export_button = gtk::Button::builder()
+        .icon_name("document-save-as-symbolic")
+        .tooltip_text("Save as PNG…")
+        .build();
+
+    let window_export = window.clone();
+    let sticker_export = sticker.clone();
+    let image_path_export = image_path.clone();
+    export_button.connect_clicked(move |_| {
+        let Some(png) = window_export
+            .renderer()
+            .and_then(|renderer| sticker_export.render_png(&renderer))
+        else {
+            return;
+        };
+        let stem = Path::new(&image_path_export)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_else(|| "sticker".to_string());
+        let dialog = gtk::FileDialog::builder()
+            .title("Save Sticker as PNG")
+            .initial_name(format!("{stem}-die-cut.png"))
+            .modal(true)
+            .build();
+        dialog.save(
+            Some(&window_export),
+            gtk::gio::Cancellable::NONE,
+            move |result| {
+                if let Ok(file) = result
+                    && let Some(path) = file.path()
+                    && let Err(err) = std::fs::write(&path, &png)
+                {
+                    eprintln!("Failed to save {}: {err}", path.display());
+                }
+            },
+        );
+    });
+
+    let die_cut_row = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+    die_cut_row.append(&outline_color_button);
+    die_cut_row.append(&shadow_check);
+    die_cut_row.append(&export_button);
+
+    // Lock button in popover; a locked sticker ignores the pointer entirely, so it is
+    // unlocked again from the main window or the app shortcut
+    let lock_button = gtk::ToggleButton::builder()
+        .icon_name("changes-prevent-symbolic")
+        .tooltip_text("Lock and Click Through")
+        .active(state.borrow().locked)
+        .build();
+
+    let state_lock = state.clone();
+    let window_lock = window.clone();
+    let popover_lock = popover.clone();
+    lock_button.connect_toggled(move |button| {
+        state_lock.borrow_mut().locked = button.is_active();
+        apply_lock(&window_lock, button.is_active());
+        if button.is_active() {
+            popover_lock.popdown();
         }
     });
 
+    // The sticker may have been unlocked elsewhere since the popover was last shown
+    let state_lock_show = state.clone();
+    let lock_button_show = lock_button.clone();
+    popover.connect_show(move |_| {
+        let locked = state_lock_show.borrow().locked;
+        lock_button_show.set_active(locked);
+    });
+
     // Close button in popover
     let close_button = gtk::Button::builder()
         .icon_name("window-close-symbolic")
@@ -158,8 +370,16 @@ pub fn create_sticker_window(
         window_close.close();
     });
 
-    popover_box.append(&rotate_button);
-    popover_box.append(&close_button);
+    button_row.append(&rotate_button);
+    button_row.append(&flip_horizontal_button);
+    button_row.append(&flip_vertical_button);
+    button_row.append(&lock_button);
+    button_row.append(&close_button);
+    popover_box.append(&button_row);
+    popover_box.append(&rotation_scale);
+    popover_box.append(&opacity_scale);
+    popover_box.append(&outline_scale);
+    popover_box.append(&die_cut_row);
     popover.set_child(Some(&popover_box));
     popover.set_parent(&aspect_frame);
 
@@ -188,29 +408,40 @@ pub fn create_sticker_window(
 
     aspect_frame.add_controller(double_click);
 
-    // Scroll-to-scale gesture
-    let scale_clone = scale.clone();
-    let aspect_ratio_for_scale = aspect_ratio;
-    let rotation_angle_for_scale = rotation_angle.clone();
+    // Scroll-to-scale gesture, or rotate while Shift is held and fade while Ctrl is held
+    let state_scale = state.clone();
 
-    let scroll = gtk::EventControllerScroll::new(gtk::EventControllerScrollFlags::VERTICAL);
+    let scroll = gtk::EventControllerScroll::new(gtk::EventControllerScrollFlags::BOTH_AXES);
 
     let scroll_window = window.clone();
-    scroll.connect_scroll(move |_, _, dy| {
-        let mut s = scale_clone.borrow_mut();
-        let delta = (-dy as f32 * 0.1).clamp(-0.2, 0.2);
-        *s = (*s + delta).clamp(0.2, 5.0);
-
-        let angle = *rotation_angle_for_scale.borrow();
-        let ratio = if angle == 90 || angle == 270 {
-            1.0 / aspect_ratio_for_scale
-        } else {
-            aspect_ratio_for_scale
+    let image_size_scroll = image_size.clone();
+    scroll.connect_scroll(move |controller, dx, dy| {
+        if state_scale.borrow().locked {
+            return glib::Propagation::Proceed;
+        }
+        let modifiers = controller.current_event_state();
+        if modifiers.contains(gdk::ModifierType::CONTROL_MASK) {
+            opacity_scale.set_value(opacity_scale.value() - dy * 0.05);
+            return glib::Propagation::Stop;
+        }
+        if modifiers.contains(gdk::ModifierType::SHIFT_MASK) {
+            // Some platforms turn Shift+wheel into horizontal scrolling
+            let rotation = state_scale.borrow().rotation;
+            let delta = ((dy + dx) * 5.0).round() as i32;
+            rotation_scale.set_value((rotation + delta).rem_euclid(360) as f64);
+            return glib::Propagation::Stop;
+        }
+
+        let state = {
+            let mut state = state_scale.borrow_mut();
+            let delta = (-dy as f32 * 0.1).clamp(-0.2, 0.2);
+            state.scale = (state.scale + delta).clamp(0.2, 5.0);
+            state.clone()
         };
 
-        let w = (image_width as f32 * *s).max(25.0) as i32;
-        let h = (w as f32 / ratio).max(25.0) as i32;
-        scroll_window.set_default_size(w, h);
+        if let Some(image_size) = image_size_scroll.get() {
+            apply_size(&scroll_window, &state, image_size);
+        }
 
         glib::Propagation::Proceed
     });
@@ -221,19 +452,23 @@ pub fn create_sticker_window(
     let drag = gtk::GestureDrag::new();
 
     let drag_window = window.clone();
+    let state_drag = state.clone();
     drag.connect_drag_begin(move |gesture, start_x, start_y| {
+        if state_drag.borrow().locked {
+            return;
+        }
         gesture.set_state(gtk::EventSequenceState::Claimed);
-        if let Some(surface) = drag_window.surface() {
-            if let Ok(toplevel) = surface.downcast::<gdk::Toplevel>() {
-                let device = gesture.device().unwrap();
-                toplevel.begin_move(
-                    &device,
-                    gesture.current_button() as i32,
-                    start_x,
-                    start_y,
-                    gesture.current_event_time(),
-                );
-            }
+        if let Some(surface) = drag_window.surface()
+            && let Ok(toplevel) = surface.downcast::<gdk::Toplevel>()
+        {
+            let device = gesture.device().unwrap();
+            toplevel.begin_move(
+                &device,
+                gesture.current_button() as i32,
+                start_x,
+                start_y,
+                gesture.current_event_time(),
+            );
         }
     });
 
@@ -250,16 +485,20 @@ pub fn create_sticker_window(
 
     window.set_child(Some(&aspect_frame));
 
-    let anim_source_id_close = anim_source_id.clone();
+    // The input region lives on the surface, which only exists once realized
+    let state_realize = state.clone();
+    window.connect_realize(move |window| {
+        if state_realize.borrow().locked {
+            apply_lock(window, true);
+        }
+    });
+
     let window_for_removal = window.clone();
     let child_windows_close = child_windows.clone();
     window.connect_close_request(move |_| {
-        if let Some(id) = anim_source_id_close.borrow_mut().take() {
-            id.remove();
-        }
         child_windows_close
             .borrow_mut()
-            .retain(|w| w != &window_for_removal);
+            .retain(|handle| handle.window != window_for_removal);
         glib::Propagation::Proceed
     });
 
@@ -290,6 +529,326 @@ pub fn create_sticker_window(
         gtk::STYLE_PROVIDER_PRIORITY_USER,
     );
 
+    // Vector stickers are drawn again at every zoom level and display scale
+    let svg_render = if frames::is_svg(Path::new(&image_path)) {
+        let renderer = SvgRenderer::new(&image_path, &sticker);
+        let image_size_svg = image_size.clone();
+        let sticker_svg = sticker.clone();
+        // Runs while the window is being resized, which can happen with the
+        // state borrowed, so the scale is worked out from the window itself
+        let request_render = Rc::new(move |window: &gtk::ApplicationWindow| {
+            let Some((width, height)) = image_size_svg.get() else {
+                return;
+            };
+            let (bounds_width, _) = sticker_svg.bounds(width as f64, height as f64);
+            let scale = window.default_width() as f64 / bounds_width * window.scale_factor() as f64;
+            renderer.request(
+                (width as f64 * scale).round() as i32,
+                (height as f64 * scale).round() as i32,
+            );
+        });
+
+        let request_resize = request_render.clone();
+        window.connect_default_width_notify(move |window| request_resize(window));
+        let request_scale = request_render.clone();
+        window.connect_scale_factor_notify(move |window| request_scale(window));
+        Some(request_render)
+    } else {
+        None
+    };
+
+    // Shows the decoded image, or lets the picture try the file itself if it
+    // could not be decoded
+    let show_image = {
+        let window = window.downgrade();
+        let picture = picture.clone();
+        let sticker = sticker.clone();
+        let popover_box = popover_box.clone();
+        let state = state.clone();
+        let image_size = image_size.clone();
+        let image_path = image_path.clone();
+        move |shared: Option<AnimatedPaintable>| {
+            let Some(window) = window.upgrade() else {
+                return;
+            };
+            let attached = shared.is_some_and(|shared| {
+                attach_image(&window, &sticker, &popover_box, &state, &image_size, shared)
+            });
+            if !attached {
+                picture.set_filename(Some(&image_path));
+            }
+            if let Some(render) = &svg_render {
+                render(&window);
+            }
+        }
+    };
+
+    // Windows showing the same file share one set of decoded frames; anything
+    // else is decoded off the main thread, since a Lottie sticker can take a
+    // while to render
+    if !is_video {
+        match animation::lookup_shared(&image_path, None) {
+            Some(shared) => show_image(Some(shared)),
+            None => {
+                let image_path = image_path.clone();
+                glib::spawn_future_local(async move {
+                    let path = image_path.clone();
+                    let decoded = gio::spawn_blocking(move || frames::decode(Path::new(&path)));
+                    match decoded.await {
+                        Ok(Ok(frames)) => {
+                            show_image(Some(animation::share(&image_path, None, &frames)))
+                        }
+                        _ => show_image(None),
+                    }
+                });
+            }
+        }
+    }
+
     window.present();
-    window
+    static NEXT_ID: AtomicU32 = AtomicU32::new(1);
+    StickerHandle {
+        id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+        window,
+        state,
+        image_size,
+        board: None,
+    }
+}
+
+/// Shows decoded frames in the window, sizing it to the image or to its saved
+/// size, and adds playback controls for animations. Returns false if the image
+/// turned out to be empty.
+fn attach_image(
+    window: &gtk::ApplicationWindow,
+    sticker: &StickerPaintable,
+    popover_box: &gtk::Box,
+    state: &Rc<RefCell<StickerState>>,
+    image_size: &Cell<Option<(i32, i32)>>,
+    shared: AnimatedPaintable,
+) -> bool {
+    let (width, height) = (shared.intrinsic_width(), shared.intrinsic_height());
+    if width <= 0 || height <= 0 {
+        return false;
+    }
+    image_size.set(Some((width.max(25), height.max(25))));
+
+    let playback = {
+        let state = state.borrow();
+        if state.width > 0 && state.height > 0 {
+            window.set_default_size(state.width, state.height);
+            let (bounds_width, bounds_height) = sticker_bounds(
+                width as f64,
+                height as f64,
+                state.rotation as f64,
+                &state.die_cut,
+            );
+            if let Some(aspect_frame) = window.child().and_downcast::<gtk::AspectFrame>() {
+                aspect_frame.set_ratio((bounds_width / bounds_height) as f32);
+            }
+        } else {
+            apply_size(window, &state, (width.max(25), height.max(25)));
+        }
+        state.playback
+    };
+
+    let own = if playback == Playback::default() {
+        shared
+    } else {
+        shared.with_playback(playback)
+    };
+    sticker.set_inner(Some(own.upcast_ref()));
+    if own.is_animated() {
+        popover_box.append(&create_playback_controls(sticker, own, state));
+    }
+    true
+}
+
+/// Builds the pause, frame-step, mode and speed controls for an animated
+/// sticker. Every change is written back to the sticker's saved state.
+fn create_playback_controls(
+    sticker: &StickerPaintable,
+    paintable: AnimatedPaintable,
+    state: &Rc<RefCell<StickerState>>,
+) -> gtk::Box {
+    let paintable = Rc::new(RefCell::new(paintable));
+    let controls = gtk::Box::new(gtk::Orientation::Vertical, 6);
+    let row = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+
+    let step_back = gtk::Button::builder()
+        .icon_name("media-skip-backward-symbolic")
+        .tooltip_text("Previous Frame")
+        .build();
+    let pause_button = gtk::Button::new();
+    let step_forward = gtk::Button::builder()
+        .icon_name("media-skip-forward-symbolic")
+        .tooltip_text("Next Frame")
+        .build();
+    update_pause_button(&pause_button, state.borrow().playback.paused);
+
+    let sticker_pause = sticker.clone();
+    let paintable_pause = paintable.clone();
+    let state_pause = state.clone();
+    pause_button.connect_clicked(move |button| {
+        let mut state = state_pause.borrow_mut();
+        let paintable = own_paintable(&sticker_pause, &paintable_pause, state.playback);
+        state.playback.paused = !state.playback.paused;
+        paintable.set_paused(state.playback.paused);
+        update_pause_button(button, state.playback.paused);
+    });
+
+    for (button, forward) in [(&step_back, false), (&step_forward, true)] {
+        let sticker = sticker.clone();
+        let paintable = paintable.clone();
+        let state = state.clone();
+        let pause_button = pause_button.clone();
+        button.connect_clicked(move |_| {
+            let mut state = state.borrow_mut();
+            // Stepping pauses so the chosen frame stays on screen
+            own_paintable(&sticker, &paintable, state.playback).step(forward);
+            state.playback.paused = true;
+            update_pause_button(&pause_button, true);
+        });
+    }
+
+    let mode_labels: Vec<&str> = PlaybackMode::ALL.iter().map(|mode| mode.label()).collect();
+    let mode_dropdown = gtk::DropDown::from_strings(&mode_labels);
+    mode_dropdown.set_tooltip_text(Some("Playback Direction"));
+    mode_dropdown.set_hexpand(true);
+    let current_mode = state.borrow().playback.mode;
+    if let Some(position) = PlaybackMode::ALL
+        .iter()
+        .position(|mode| *mode == current_mode)
+    {
+        mode_dropdown.set_selected(position as u32);
+    }
+
+    let sticker_mode = sticker.clone();
+    let paintable_mode = paintable.clone();
+    let state_mode = state.clone();
+    mode_dropdown.connect_selected_notify(move |dropdown| {
+        if let Some(mode) = PlaybackMode::ALL.get(dropdown.selected() as usize) {
+            let mut state = state_mode.borrow_mut();
+            own_paintable(&sticker_mode, &paintable_mode, state.playback).set_mode(*mode);
+            state.playback.mode = *mode;
+        }
+    });
+
+    row.append(&step_back);
+    row.append(&pause_button);
+    row.append(&step_forward);
+    row.append(&mode_dropdown);
+
+    // The slider works in powers of two so 1× sits in the middle
+    let speed_scale = gtk::Scale::with_range(gtk::Orientation::Horizontal, -2.0, 2.0, 0.25);
+    speed_scale.set_tooltip_text(Some("Playback Speed"));
+    speed_scale.set_draw_value(false);
+    speed_scale.set_size_request(200, -1);
+    for (value, label) in [
+        (-2.0, "¼×"),
+        (-1.0, "½×"),
+        (0.0, "1×"),
+        (1.0, "2×"),
+        (2.0, "4×"),
+    ] {
+        speed_scale.add_mark(value, gtk::PositionType::Bottom, Some(label));
+    }
+    speed_scale.set_value(state.borrow().playback.speed.clamp(0.25, 4.0).log2() as f64);
+
+    let sticker_speed = sticker.clone();
+    let state_speed = state.clone();
+    speed_scale.connect_value_changed(move |scale| {
+        let speed = 2.0_f32.powf(scale.value() as f32);
+        let mut state = state_speed.borrow_mut();
+        own_paintable(&sticker_speed, &paintable, state.playback).set_speed(speed);
+        state.playback.speed = speed;
+    });
+
+    controls.append(&row);
+    controls.append(&speed_scale);
+    controls
+}
+
+/// Gives the window a paintable of its own before its playback changes, so
+/// other windows showing the same file keep playing as they were.
+fn own_paintable(
+    sticker: &StickerPaintable,
+    paintable: &RefCell<AnimatedPaintable>,
+    playback: Playback,
+) -> AnimatedPaintable {
+    let mut paintable = paintable.borrow_mut();
+    if paintable.is_shared() {
+        *paintable = paintable.with_playback(playback);
+        sticker.set_inner(Some(paintable.upcast_ref()));
+    }
+    paintable.clone()
+}
+
+fn update_pause_button(button: &gtk::Button, paused: bool) {
+    if paused {
+        button.set_icon_name("media-playback-start-symbolic");
+        button.set_tooltip_text(Some("Play"));
+    } else {
+        button.set_icon_name("media-playback-pause-symbolic");
+        button.set_tooltip_text(Some("Pause"));
+    }
+}
+
+/// Makes the window ignore pointer input while locked by giving its surface an
+/// empty input region.
+fn apply_lock(window: &gtk::ApplicationWindow, locked: bool) {
+    let Some(surface) = window.surface() else {
+        return;
+    };
+    let region = if locked {
+        gtk::cairo::Region::create()
+    } else {
+        // Larger than any sticker; the compositor clips it to the surface. X11 shapes
+        // use 16-bit coordinates.
+        gtk::cairo::Region::create_rectangle(&gtk::cairo::RectangleInt::new(
+            0,
+            0,
+            i16::MAX as i32,
+            i16::MAX as i32,
+        ))
+    };
+    surface.set_input_region(&region);
+}
+
+/// Redraws the sticker for its rotation and flips and resizes the window to fit.
+fn apply_transform(
+    window: &gtk::ApplicationWindow,
+    sticker: &StickerPaintable,
+    state: &StickerState,
+    image_size: Option<(i32, i32)>,
+) {
+    sticker.set_transform(
+        state.rotation as f64,
+        state.flip_horizontal,
+        state.flip_vertical,
+    );
+    if let Some(image_size) = image_size {
+        apply_size(window, state, image_size);
+    }
+}
+
+/// Sizes the window to the bounding box of the scaled, rotated sticker and its
+/// outline and shadow.
+fn apply_size(
+    window: &gtk::ApplicationWindow,
+    state: &StickerState,
+    (image_width, image_height): (i32, i32),
+) {
+    let (width, height) = sticker_bounds(
+        image_width as f64 * state.scale as f64,
+        image_height as f64 * state.scale as f64,
+        state.rotation as f64,
+        &state.die_cut,
+    );
+    let w = (width.round() as i32).max(25);
+    let h = (height.round() as i32).max(25);
+    window.set_default_size(w, h);
+    if let Some(aspect_frame) = window.child().and_downcast::<gtk::AspectFrame>() {
+        aspect_frame.set_ratio(width as f32 / height as f32);
+    }
 }
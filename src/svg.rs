@@ -0,0 +1,96 @@
+use gtk::glib;
+use gtk::prelude::*;
+use std::cell::{Cell, RefCell};
+use std::path::Path;
+use std::rc::Rc;
+use std::time::Duration;
+
+use crate::frames;
+use crate::sticker_paintable::StickerPaintable;
+
+// Wait for scrolling to settle before re-rendering
+const RENDER_DELAY: Duration = Duration::from_millis(150);
+// Largest side rendered, to keep extreme zoom levels from exhausting memory
+const MAX_RENDER_SIZE: i32 = 4096;
+
+/// Re-renders an SVG sticker at the size it is displayed at, so vector art
+/// stays sharp instead of stretching a bitmap.
+#[derive(Clone)]
+pub struct SvgRenderer {
+    inner: Rc<SvgRendererInner>,
+}
+
+struct SvgRendererInner {
+    path: String,
+    sticker: glib::WeakRef<StickerPaintable>,
+    pending: RefCell<Option<glib::SourceId>>,
+    // Size last asked for, so repeated requests for it are ignored
+    requested: Cell<(i32, i32)>,
+    // Bumped for every render so a slow, outdated one never replaces a newer one
+    generation: Cell<u64>,
+}
+
+impl SvgRenderer {
+    pub fn new(path: &str, sticker: &StickerPaintable) -> Self {
+        Self {
+            inner: Rc::new(SvgRendererInner {
+                path: path.to_string(),
+                sticker: sticker.downgrade(),
+                pending: RefCell::new(None),
+                requested: Cell::new((0, 0)),
+                generation: Cell::new(0),
+            }),
+        }
+    }
+
+    /// Schedules a render at `width` × `height` device pixels once resizing
+    /// has settled.
+    pub fn request(&self, width: i32, height: i32) {
+        let size = (
+            width.clamp(1, MAX_RENDER_SIZE),
+            height.clamp(1, MAX_RENDER_SIZE),
+        );
+        if self.inner.requested.replace(size) == size {
+            return;
+        }
+
+        if let Some(id) = self.inner.pending.take() {
+            id.remove();
+        }
+        let weak = Rc::downgrade(&self.inner);
+        let id = glib::timeout_add_local_once(RENDER_DELAY, move || {
+            if let Some(inner) = weak.upgrade() {
+                inner.pending.take();
+                SvgRenderer { inner }.render(size);
+            }
+        });
+        self.inner.pending.replace(Some(id));
+    }
+
+    fn render(&self, (width, height): (i32, i32)) {
+        let generation = self.inner.generation.get() + 1;
+        self.inner.generation.set(generation);
+
+        let inner = self.inner.clone();
+        glib::spawn_future_local(async move {
+            let path = inner.path.clone();
+            let rendered = gio::spawn_blocking(move || {
+                frames::decode_at_size(Path::new(&path), width, height)
+            })
+            .await;
+            if inner.generation.get() != generation {
+                return;
+            }
+            match rendered {
+                Ok(Ok(frames)) => {
+                    if let (Some(sticker), Some(frame)) = (inner.sticker.upgrade(), frames.first())
+                    {
+                        sticker.set_inner(Some(frame.texture().upcast_ref()));
+                    }
+                }
+                Ok(Err(err)) => eprintln!("Failed to render {}: {err}", inner.path),
+                Err(_) => eprintln!("SVG renderer panicked on {}", inner.path),
+            }
+        });
+    }
+}
@@ -0,0 +1,180 @@
+use image::ImageFormat;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::frames::{self, DecodeResult, Frame};
+
+// Part of every cache key; bump it when decoding changes so old thumbnails are rebuilt
+const CACHE_VERSION: u32 = 2;
+// Entries unused for this long are removed when pruning
+const MAX_AGE: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+// Past this total, the least recently used entries are removed as well
+const MAX_SIZE: u64 = 256 * 1024 * 1024;
+
+/// Describes a cached thumbnail; frame N is stored next to it as `N.png`.
+#[derive(Serialize, Deserialize)]
+struct CacheIndex {
+    delays_ms: Vec<u64>,
+}
+
+/// Returns downscaled frames for an image, decoding the original only when the
+/// cache has no entry for its current path, modification time and size.
+pub fn load(path: &Path, size: u32) -> DecodeResult {
+    if let Ok(frames) = lookup(path, size) {
+        return Ok(frames);
+    }
+
+    let frames = frames::decode_thumbnail(path, size)?;
+    store(path, size, &frames);
+    Ok(frames)
+}
+
+/// Returns cached frames without ever decoding the original.
+pub fn lookup(path: &Path, size: u32) -> DecodeResult {
+    let dir = cache_dir().join(cache_key(path, size)?);
+    let frames = read_entry(&dir)?;
+    // The index's modification time records when the entry was last used
+    let _ = File::options()
+        .write(true)
+        .open(dir.join("index.json"))
+        .and_then(|index| index.set_modified(SystemTime::now()));
+    Ok(frames)
+}
+
+/// Removes entries that have not been used for a month, then the least
+/// recently used ones until the cache fits in `MAX_SIZE`. Entries are keyed by
+/// modification time and size, so every edit of a file leaves an old one
+/// behind. Runs off the main thread.
+pub fn prune() {
+    let Ok(entries) = fs::read_dir(cache_dir()) else {
+        return;
+    };
+    let now = SystemTime::now();
+    let mut kept = Vec::new();
+    for entry in entries.flatten() {
+        let dir = entry.path();
+        let last_used = fs::metadata(dir.join("index.json"))
+            .or_else(|_| entry.metadata())
+            .and_then(|metadata| metadata.modified())
+            .unwrap_or(UNIX_EPOCH);
+        if now.duration_since(last_used).unwrap_or_default() > MAX_AGE {
+            let _ = fs::remove_dir_all(&dir);
+        } else {
+            kept.push((last_used, entry_size(&dir), dir));
+        }
+    }
+
+    kept.sort_by_key(|(last_used, _, _)| *last_used);
+    let mut total: u64 = kept.iter().map(|(_, size, _)| size).sum();
+    for (_, size, dir) in kept {
+        if total <= MAX_SIZE {
+            break;
+        }
+        if fs::remove_dir_all(&dir).is_ok() {
+            total -= size;
+        }
+    }
+}
+
+fn entry_size(dir: &Path) -> u64 {
+    fs::read_dir(dir)
+        .map(|files| {
+            files
+                .flatten()
+                .filter_map(|file| file.metadata().ok())
+                .map(|metadata| metadata.len())
+                .sum()
+        })
+        .unwrap_or(0)
+}
+
+/// Caches frames that were decoded elsewhere, such as video frames that can
+/// only be taken on the main thread. Failures are only logged.
+pub fn store(path: &Path, size: u32, frames: &[Frame]) {
+    let result = cache_key(path, size)
+        .map_err(Into::into)
+        .and_then(|key| write_entry(&cache_dir().join(key), frames));
+    if let Err(err) = result {
+        eprintln!("Failed to cache thumbnail for {}: {err}", path.display());
+    }
+}
+
+fn cache_key(path: &Path, size: u32) -> std::io::Result<String> {
+    let metadata = fs::metadata(path)?;
+    let mtime = metadata
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+
+    let mut hasher = Sha256::new();
+    hasher.update(CACHE_VERSION.to_le_bytes());
+    hasher.update(path.to_string_lossy().as_bytes());
+    hasher.update(mtime.to_le_bytes());
+    hasher.update(metadata.len().to_le_bytes());
+    hasher.update(size.to_le_bytes());
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+fn read_entry(dir: &Path) -> DecodeResult {
+    let index: CacheIndex = serde_json::from_str(&fs::read_to_string(dir.join("index.json"))?)?;
+    index
+        .delays_ms
+        .iter()
+        .enumerate()
+        .map(|(number, delay)| {
+            let image = image::open(dir.join(format!("{number}.png")))?.into_rgba8();
+            Ok(Frame {
+                image,
+                delay: Duration::from_millis(*delay),
+            })
+        })
+        .collect()
+}
+
+fn write_entry(dir: &Path, frames: &[Frame]) -> Result<(), Box<dyn std::error::Error>> {
+    // Build the entry under a temporary name so readers never see half of it
+    static PARTIAL_COUNTER: AtomicU64 = AtomicU64::new(0);
+    let partial = dir.with_extension(format!(
+        "partial-{}-{}",
+        std::process::id(),
+        PARTIAL_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    if partial.exists() {
+        fs::remove_dir_all(&partial)?;
+    }
+    fs::create_dir_all(&partial)?;
+
+    for (number, frame) in frames.iter().enumerate() {
+        frame
+            .image
+            .save_with_format(partial.join(format!("{number}.png")), ImageFormat::Png)?;
+    }
+    let index = CacheIndex {
+        delays_ms: frames
+            .iter()
+            .map(|frame| frame.delay.as_millis() as u64)
+            .collect(),
+    };
+    fs::write(partial.join("index.json"), serde_json::to_string(&index)?)?;
+
+    if let Err(err) = fs::rename(&partial, dir) {
+        fs::remove_dir_all(&partial)?;
+        // Another thread may have cached the same thumbnail first
+        if !dir.exists() {
+            return Err(err.into());
+        }
+    }
+    Ok(())
+}
+
+fn cache_dir() -> PathBuf {
+    let mut path = dirs::cache_dir().unwrap_or_else(|| PathBuf::from("."));
+    path.push("stickerbook");
+    path.push("thumbnails");
+    path
+}
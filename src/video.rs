@@ -0,0 +1,90 @@
+use gtk::prelude::*;
+use gtk::{gdk, glib};
+use image::RgbaImage;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::frames::{self, DecodeResult, Frame};
+use crate::thumbnail_cache;
+
+// How long to wait for GStreamer to produce the first frame of a thumbnail
+const FIRST_FRAME_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Returns true for WebM (and other Matroska) files, which start with an EBML
+/// signature.
+pub fn is_video(path: &Path) -> bool {
+    let mut header = [0u8; 4];
+    File::open(path)
+        .and_then(|mut file| file.read_exact(&mut header))
+        .is_ok()
+        && header == [0x1a, 0x45, 0xdf, 0xa3]
+}
+
+/// Starts a muted, looping stream for a video sticker. GTK plays it through
+/// GStreamer, which keeps VP9 alpha when its alpha-aware decoder is installed.
+pub fn open(path: &str) -> gtk::MediaFile {
+    let media = gtk::MediaFile::for_filename(path);
+    media.set_muted(true);
+    media.set_loop(true);
+    media.play();
+    media
+}
+
+/// Returns the first frame of a video scaled for the library grid, from the
+/// thumbnail cache when possible. Must run on the main thread, since frames
+/// come out of GTK's media stream.
+pub async fn thumbnail(path: &Path, size: u32) -> DecodeResult {
+    if let Ok(frames) = thumbnail_cache::lookup(path, size) {
+        return Ok(frames);
+    }
+
+    let image = first_frame(path).await?;
+    let frames = vec![Frame {
+        image: frames::scale_to_short_side(image, size),
+        delay: Duration::ZERO,
+    }];
+    thumbnail_cache::store(path, size, &frames);
+    Ok(frames)
+}
+
+async fn first_frame(path: &Path) -> Result<RgbaImage, Box<dyn std::error::Error + Send + Sync>> {
+    let media = gtk::MediaFile::for_filename(path);
+    media.set_muted(true);
+    media.play();
+
+    // Poll until the stream has decoded a frame; the stream is dropped afterwards
+    let poll_interval = Duration::from_millis(50);
+    let mut waited = Duration::ZERO;
+    let texture = loop {
+        if let Some(err) = media.error() {
+            return Err(err.into());
+        }
+        if let Ok(texture) = media.current_image().downcast::<gdk::Texture>()
+            && texture.width() > 0
+            && texture.height() > 0
+        {
+            break texture;
+        }
+        if waited >= FIRST_FRAME_TIMEOUT {
+            return Err("Timed out waiting for the first video frame".into());
+        }
+        glib::timeout_future(poll_interval).await;
+        waited += poll_interval;
+    };
+    media.pause();
+
+    let mut downloader = gdk::TextureDownloader::new(&texture);
+    downloader.set_format(gdk::MemoryFormat::R8g8b8a8);
+    let (bytes, stride) = downloader.download_bytes();
+    let width = texture.width() as usize;
+    let height = texture.height() as usize;
+
+    let mut data = Vec::with_capacity(width * height * 4);
+    for row in 0..height {
+        let start = row * stride;
+        data.extend_from_slice(&bytes[start..start + width * 4]);
+    }
+    RgbaImage::from_raw(width as u32, height as u32, data).ok_or_else(|| "bad video frame".into())
+}